@@ -0,0 +1,275 @@
+//! Checks for the deterministic encoding rules of Cosmos SDK ADR-027.
+//!
+//! See https://github.com/cosmos/cosmos-sdk/blob/main/docs/architecture/adr-027-deterministic-protobuf-serialization.md
+//!
+//! Since no schema is available, the rule that no unknown fields may be present
+//! cannot be checked. Repeated fields are recognized by their field number occurring
+//! more than once in a message. Repeated varint and fixed-width fields are reported as
+//! not packed.
+
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+use crate::parse::{direct_fields, Entry, EntryValue, VarintPart};
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum ViolationKind {
+    /// The field number is lower than the one of the previous field in the same message
+    FieldOrder { previous: u64 },
+    /// A singular field contains the default value, which must be omitted
+    DefaultValue,
+    /// A varint uses more bytes than necessary
+    NonMinimalVarint(VarintPart),
+    /// A repeated scalar field is not packed into a single length-delimited field
+    NotPacked,
+}
+
+/// A violation of the deterministic encoding rules
+#[derive(Debug, PartialEq, Clone)]
+pub struct Violation {
+    pub path: Vec<u64>,
+    /// Offset of the field in the input
+    pub offset: usize,
+    pub kind: ViolationKind,
+}
+
+impl fmt::Display for ViolationKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ViolationKind::FieldOrder { previous } => write!(
+                f,
+                "field appears after field {} but fields must be in ascending order",
+                previous
+            ),
+            ViolationKind::DefaultValue => write!(f, "default value must be omitted"),
+            ViolationKind::NonMinimalVarint(part) => {
+                write!(f, "{} varint is longer than necessary", part.name())
+            }
+            ViolationKind::NotPacked => write!(f, "repeated scalar field must be packed"),
+        }
+    }
+}
+
+/// Returns all violations of the deterministic encoding rules in the entries
+/// as returned by `try_parse_entries`.
+pub fn check_canonical(entries: &[Entry]) -> Vec<Violation> {
+    let mut out = Vec::new();
+    check_message(entries, &mut out);
+    out
+}
+
+/// Checks the fields of a single message. The entries are the direct children of the message
+/// including the entries of nested messages.
+fn check_message(entries: &[Entry], out: &mut Vec<Violation>) {
+    let fields = direct_fields(entries);
+    let mut counts = HashMap::<u64, usize>::new();
+    for (index, _) in &fields {
+        *counts
+            .entry(*entries[*index].path.last().unwrap())
+            .or_default() += 1;
+    }
+
+    let mut previous: Option<u64> = None;
+    let mut seen = HashSet::<u64>::new();
+    for (index, end) in fields.iter().copied() {
+        let entry = &entries[index];
+        let number = *entry.path.last().unwrap();
+        let repeated = counts[&number] > 1;
+        let first = seen.insert(number);
+        let mut violation = |kind: ViolationKind| {
+            out.push(Violation {
+                path: entry.path.clone(),
+                offset: entry.span.start,
+                kind,
+            })
+        };
+
+        if let Some(previous) = previous {
+            if number < previous {
+                violation(ViolationKind::FieldOrder { previous });
+            }
+        }
+        previous = Some(number);
+
+        for part in &entry.span.overlong {
+            violation(ViolationKind::NonMinimalVarint(*part));
+        }
+
+        if !repeated && is_default(&entry.value) {
+            violation(ViolationKind::DefaultValue);
+        }
+
+        // Reported once per field number
+        if repeated && first && is_scalar(&entry.value) {
+            violation(ViolationKind::NotPacked);
+        }
+
+        if entry.value == EntryValue::OpenNested {
+            check_message(&entries[index + 1..end], out);
+        }
+    }
+}
+
+/// Checks if the value is a varint or fixed-width value, which can be packed
+fn is_scalar(value: &EntryValue) -> bool {
+    matches!(
+        value,
+        EntryValue::Varint(_) | EntryValue::Fixed64(_) | EntryValue::Fixed32(_)
+    )
+}
+
+/// Checks if the value is the default value of its type. For length-delimited fields
+/// this is the empty string or bytes value.
+fn is_default(value: &EntryValue) -> bool {
+    match value {
+        EntryValue::Varint(v) => *v == 0,
+        EntryValue::Fixed64(v) => *v == [0; 8],
+        EntryValue::Fixed32(v) => *v == [0; 4],
+        EntryValue::Bytes(v) => v.is_empty(),
+        EntryValue::OpenNested | EntryValue::CloseNested => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse::{try_parse_entries, ParseConfig};
+
+    fn check(bytes: &[u8]) -> Vec<Violation> {
+        check_canonical(&try_parse_entries(bytes, ParseConfig::default()).unwrap())
+    }
+
+    #[test]
+    fn check_canonical_accepts_canonical() {
+        assert_eq!(check(b"\x08\x01\x12\x02ab\x1a\x02\x08\x01"), vec![]);
+        // repeated fields
+        assert_eq!(check(b"\x0a\x02\x01\x02\x12\x02ab\x12\x02cd"), vec![]);
+        // default values in repeated fields
+        assert_eq!(check(b"\x12\x00\x12\x02ab"), vec![]);
+    }
+
+    #[test]
+    fn check_canonical_finds_field_order() {
+        assert_eq!(
+            check(b"\x12\x02ab\x08\x01"),
+            vec![Violation {
+                path: vec![1],
+                offset: 4,
+                kind: ViolationKind::FieldOrder { previous: 2 },
+            }]
+        );
+        // in nested message
+        assert_eq!(
+            check(b"\x08\x01\x1a\x04\x10\x01\x08\x01"),
+            vec![Violation {
+                path: vec![3, 1],
+                offset: 6,
+                kind: ViolationKind::FieldOrder { previous: 2 },
+            }]
+        );
+        // order of nested fields does not affect parent
+        assert_eq!(check(b"\x08\x01\x1a\x02\x10\x01\x20\x01"), vec![]);
+    }
+
+    #[test]
+    fn check_canonical_finds_default_values() {
+        assert_eq!(
+            check(b"\x08\x00"),
+            vec![Violation {
+                path: vec![1],
+                offset: 0,
+                kind: ViolationKind::DefaultValue,
+            }]
+        );
+        assert_eq!(
+            check(b"\x08\x01\x12\x00"),
+            vec![Violation {
+                path: vec![2],
+                offset: 2,
+                kind: ViolationKind::DefaultValue,
+            }]
+        );
+        assert_eq!(
+            check(b"\x0d\x00\x00\x00\x00\x11\x00\x00\x00\x00\x00\x00\x00\x00")
+                .into_iter()
+                .map(|v| v.path)
+                .collect::<Vec<_>>(),
+            vec![vec![1], vec![2]]
+        );
+    }
+
+    #[test]
+    fn check_canonical_finds_non_minimal_varints() {
+        assert_eq!(
+            check(b"\x88\x00\x80\x01"),
+            vec![Violation {
+                path: vec![1],
+                offset: 0,
+                kind: ViolationKind::NonMinimalVarint(VarintPart::Tag),
+            }]
+        );
+        assert_eq!(
+            check(b"\x08\x81\x00"),
+            vec![Violation {
+                path: vec![1],
+                offset: 0,
+                kind: ViolationKind::NonMinimalVarint(VarintPart::Value),
+            }]
+        );
+    }
+
+    #[test]
+    fn check_canonical_finds_unpacked_fields() {
+        assert_eq!(
+            check(b"\x08\x01\x08\x02\x08\x03\x12\x02ab"),
+            vec![Violation {
+                path: vec![1],
+                offset: 0,
+                kind: ViolationKind::NotPacked,
+            }]
+        );
+        // default values in repeated fields are no violation of their own
+        assert_eq!(
+            check(b"\x08\x01\x15\x00\x00\x00\x00\x15\x00\x00\x00\x00"),
+            vec![Violation {
+                path: vec![2],
+                offset: 2,
+                kind: ViolationKind::NotPacked,
+            }]
+        );
+    }
+
+    #[test]
+    fn check_canonical_requires_varints_of_64_bits() {
+        // Varints of more than 10 bytes or 64 bits are no valid serialization,
+        // so they cannot be checked at all
+        let mut bytes = vec![0x08];
+        bytes.extend([0xff; 19]);
+        bytes.push(0x7f);
+        assert_eq!(try_parse_entries(&bytes, ParseConfig::default()), None);
+        let mut bytes = vec![0x88];
+        bytes.extend([0x80; 10]);
+        bytes.extend(b"\x00\x01");
+        assert_eq!(try_parse_entries(&bytes, ParseConfig::default()), None);
+    }
+
+    #[test]
+    fn violation_kind_display_works() {
+        assert_eq!(
+            ViolationKind::FieldOrder { previous: 3 }.to_string(),
+            "field appears after field 3 but fields must be in ascending order"
+        );
+        assert_eq!(
+            ViolationKind::DefaultValue.to_string(),
+            "default value must be omitted"
+        );
+        assert_eq!(
+            ViolationKind::NonMinimalVarint(VarintPart::Length).to_string(),
+            "length varint is longer than necessary"
+        );
+        assert_eq!(
+            ViolationKind::NotPacked.to_string(),
+            "repeated scalar field must be packed"
+        );
+    }
+}
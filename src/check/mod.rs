@@ -0,0 +1,3 @@
+mod canonical;
+
+pub use canonical::{check_canonical, Violation, ViolationKind};
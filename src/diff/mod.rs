@@ -0,0 +1,3 @@
+mod structural;
+
+pub use structural::{diff_messages, Difference, DifferenceKind, FieldKey};
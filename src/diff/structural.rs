@@ -0,0 +1,392 @@
+//! Comparison of two decoded messages field by field.
+//!
+//! Fields are matched by their number and, for fields occurring more than once,
+//! by their position among the fields with the same number.
+
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::parse::{FieldSpan, VarintPart};
+use crate::tree::{field_indices, Field, FieldValue, Message};
+
+/// One step of the path to a difference
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct FieldKey {
+    pub number: u64,
+    /// The position among the fields with the same number.
+    /// Only set for fields that occur more than once in either message.
+    pub index: Option<usize>,
+}
+
+impl fmt::Display for FieldKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.index {
+            Some(index) => write!(f, ".{}[{}]", self.number, index),
+            None => write!(f, ".{}", self.number),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum DifferenceKind<'a> {
+    /// The field only exists in the new message
+    Added(FieldValue<'a>),
+    /// The field only exists in the old message
+    Removed(FieldValue<'a>),
+    Changed {
+        old: FieldValue<'a>,
+        new: FieldValue<'a>,
+    },
+    /// The values are equal but a varint is encoded with a different number of bytes
+    VarintLength {
+        part: VarintPart,
+        old: usize,
+        new: usize,
+    },
+    /// The message contains the same fields but in a different order
+    FieldOrder,
+    /// The nested message is encoded as group in one message and length-delimited
+    /// in the other
+    WireType { old: u8, new: u8 },
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Difference<'a> {
+    pub path: Vec<FieldKey>,
+    pub kind: DifferenceKind<'a>,
+}
+
+impl Difference<'_> {
+    /// The field numbers of the path without positions
+    pub fn numbers(&self) -> Vec<u64> {
+        self.path.iter().map(|key| key.number).collect()
+    }
+}
+
+/// Returns all differences between two messages
+pub fn diff_messages<'a>(old: &Message<'a>, new: &Message<'a>) -> Vec<Difference<'a>> {
+    let mut out = Vec::new();
+    diff_fields(&old.fields, &new.fields, &[], &mut out);
+    out
+}
+
+fn diff_fields<'a>(
+    old: &[Field<'a>],
+    new: &[Field<'a>],
+    path: &[FieldKey],
+    out: &mut Vec<Difference<'a>>,
+) {
+    let old_indices = field_indices(old);
+    let new_indices = field_indices(new);
+    // The position in the fields by number and index
+    let positions = |fields: &[Field], indices: &[usize]| -> HashMap<(u64, usize), usize> {
+        fields
+            .iter()
+            .zip(indices)
+            .enumerate()
+            .map(|(position, (field, &index))| ((field.number, index), position))
+            .collect()
+    };
+    let old_positions = positions(old, &old_indices);
+    let new_positions = positions(new, &new_indices);
+    // A field is repeated if it has a second occurrence in either message
+    let repeated = |number: u64| {
+        old_positions.contains_key(&(number, 1)) || new_positions.contains_key(&(number, 1))
+    };
+    let key_path = |number: u64, index: usize| {
+        let mut key_path = path.to_vec();
+        key_path.push(FieldKey {
+            number,
+            index: repeated(number).then_some(index),
+        });
+        key_path
+    };
+
+    let mut old_order = Vec::new();
+    for (field, &index) in old.iter().zip(&old_indices) {
+        match new_positions.get(&(field.number, index)).copied() {
+            Some(position) => {
+                old_order.push(position);
+                diff_field(field, &new[position], &key_path(field.number, index), out);
+            }
+            None => out.push(Difference {
+                path: key_path(field.number, index),
+                kind: DifferenceKind::Removed(field.value.clone()),
+            }),
+        }
+    }
+    for (field, &index) in new.iter().zip(&new_indices) {
+        if !old_positions.contains_key(&(field.number, index)) {
+            out.push(Difference {
+                path: key_path(field.number, index),
+                kind: DifferenceKind::Added(field.value.clone()),
+            });
+        }
+    }
+
+    if old_order.windows(2).any(|pair| pair[0] > pair[1]) {
+        out.push(Difference {
+            path: path.to_vec(),
+            kind: DifferenceKind::FieldOrder,
+        });
+    }
+}
+
+/// Compares two fields with the same key
+fn diff_field<'a>(
+    old: &Field<'a>,
+    new: &Field<'a>,
+    path: &[FieldKey],
+    out: &mut Vec<Difference<'a>>,
+) {
+    let nested = match (&old.value, &new.value) {
+        (FieldValue::Message(old), FieldValue::Message(new)) => Some((old, new)),
+        (old, new) if old == new => None,
+        (old, new) => {
+            out.push(Difference {
+                path: path.to_vec(),
+                kind: DifferenceKind::Changed {
+                    old: old.clone(),
+                    new: new.clone(),
+                },
+            });
+            return;
+        }
+    };
+
+    if old.span.wire_type != new.span.wire_type {
+        out.push(Difference {
+            path: path.to_vec(),
+            kind: DifferenceKind::WireType {
+                old: old.span.wire_type,
+                new: new.span.wire_type,
+            },
+        });
+    }
+
+    let mut nested_differences = Vec::new();
+    if let Some((old, new)) = nested {
+        diff_fields(&old.fields, &new.fields, path, &mut nested_differences);
+    }
+
+    for (part, old_len, new_len) in varint_lengths(&old.span, &new.span) {
+        // A longer length prefix is expected if the nested message changed
+        let expected = part == VarintPart::Length && !nested_differences.is_empty();
+        if old_len != new_len && !expected {
+            out.push(Difference {
+                path: path.to_vec(),
+                kind: DifferenceKind::VarintLength {
+                    part,
+                    old: old_len,
+                    new: new_len,
+                },
+            });
+        }
+    }
+    out.append(&mut nested_differences);
+}
+
+/// The number of bytes of each varint of two fields with equal values.
+/// Only the tags are compared if the wire types differ.
+fn varint_lengths(old: &FieldSpan, new: &FieldSpan) -> Vec<(VarintPart, usize, usize)> {
+    let mut out = vec![(VarintPart::Tag, old.tag_len, new.tag_len)];
+    if old.wire_type != new.wire_type {
+        return out;
+    }
+    match old.wire_type {
+        0 => out.push((VarintPart::Value, old.payload.len(), new.payload.len())),
+        2 => out.push((VarintPart::Length, old.length_len, new.length_len)),
+        _ => {}
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse::ParseConfig;
+
+    fn diff<'a>(old: &'a [u8], new: &'a [u8]) -> Vec<Difference<'a>> {
+        diff_messages(
+            &Message::decode(old, ParseConfig::default()).unwrap(),
+            &Message::decode(new, ParseConfig::default()).unwrap(),
+        )
+    }
+
+    fn key(number: u64) -> FieldKey {
+        FieldKey {
+            number,
+            index: None,
+        }
+    }
+
+    #[test]
+    fn diff_messages_finds_nothing_for_equal() {
+        assert_eq!(
+            diff(b"\x08\x01\x1a\x02\x08\x01", b"\x08\x01\x1a\x02\x08\x01"),
+            vec![]
+        );
+    }
+
+    #[test]
+    fn diff_messages_finds_added_removed_and_changed() {
+        assert_eq!(
+            diff(b"\x08\x01\x10\x02", b"\x08\x03\x18\x04"),
+            vec![
+                Difference {
+                    path: vec![key(1)],
+                    kind: DifferenceKind::Changed {
+                        old: FieldValue::Varint(1),
+                        new: FieldValue::Varint(3),
+                    },
+                },
+                Difference {
+                    path: vec![key(2)],
+                    kind: DifferenceKind::Removed(FieldValue::Varint(2)),
+                },
+                Difference {
+                    path: vec![key(3)],
+                    kind: DifferenceKind::Added(FieldValue::Varint(4)),
+                },
+            ]
+        );
+        // in nested message
+        assert_eq!(
+            diff(b"\x1a\x02\x08\x01", b"\x1a\x02\x08\x02"),
+            vec![Difference {
+                path: vec![key(3), key(1)],
+                kind: DifferenceKind::Changed {
+                    old: FieldValue::Varint(1),
+                    new: FieldValue::Varint(2),
+                },
+            }]
+        );
+        // message replaced by a value
+        let differences = diff(b"\x1a\x02\x08\x01", b"\x18\x01");
+        assert_eq!(differences.len(), 1);
+        assert_eq!(differences[0].path, vec![key(3)]);
+        assert!(matches!(
+            &differences[0].kind,
+            DifferenceKind::Changed {
+                old: FieldValue::Message(_),
+                new: FieldValue::Varint(1),
+            }
+        ));
+    }
+
+    #[test]
+    fn diff_messages_aligns_repeated_fields() {
+        assert_eq!(
+            diff(b"\x08\x01\x08\x02", b"\x08\x01\x08\x03\x08\x04"),
+            vec![
+                Difference {
+                    path: vec![FieldKey {
+                        number: 1,
+                        index: Some(1),
+                    }],
+                    kind: DifferenceKind::Changed {
+                        old: FieldValue::Varint(2),
+                        new: FieldValue::Varint(3),
+                    },
+                },
+                Difference {
+                    path: vec![FieldKey {
+                        number: 1,
+                        index: Some(2),
+                    }],
+                    kind: DifferenceKind::Added(FieldValue::Varint(4)),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn diff_messages_finds_encoding_differences() {
+        assert_eq!(
+            diff(b"\x08\x01\x10\x02", b"\x10\x02\x08\x01"),
+            vec![Difference {
+                path: vec![],
+                kind: DifferenceKind::FieldOrder,
+            }]
+        );
+        assert_eq!(
+            diff(b"\x08\x01", b"\x88\x00\x81\x00"),
+            vec![
+                Difference {
+                    path: vec![key(1)],
+                    kind: DifferenceKind::VarintLength {
+                        part: VarintPart::Tag,
+                        old: 1,
+                        new: 2,
+                    },
+                },
+                Difference {
+                    path: vec![key(1)],
+                    kind: DifferenceKind::VarintLength {
+                        part: VarintPart::Value,
+                        old: 1,
+                        new: 2,
+                    },
+                },
+            ]
+        );
+        assert_eq!(
+            diff(b"\x1a\x02\x08\x01", b"\x1a\x82\x00\x08\x01"),
+            vec![Difference {
+                path: vec![key(3)],
+                kind: DifferenceKind::VarintLength {
+                    part: VarintPart::Length,
+                    old: 1,
+                    new: 2,
+                },
+            }]
+        );
+    }
+
+    #[test]
+    fn diff_messages_ignores_length_of_changed_messages() {
+        // 1 { 2: "zz..." } with 126 and 128 bytes in field 1
+        let nested = |len: u8| {
+            let mut bytes = vec![0x12, len];
+            bytes.extend(vec![b'z'; len as usize]);
+            bytes
+        };
+        let mut old = vec![0x0a, 126];
+        old.extend(nested(124));
+        let mut new = vec![0x0a, 0x80, 0x01];
+        new.extend(nested(126));
+        let differences = diff(&old, &new);
+        assert_eq!(differences.len(), 1);
+        assert_eq!(differences[0].path, vec![key(1), key(2)]);
+        assert!(matches!(
+            differences[0].kind,
+            DifferenceKind::Changed { .. }
+        ));
+    }
+
+    #[test]
+    fn diff_messages_finds_groups() {
+        // 1 { 2: 1 } as group and length-delimited
+        assert_eq!(
+            diff(b"\x0b\x10\x01\x0c", b"\x0a\x02\x10\x01"),
+            vec![Difference {
+                path: vec![key(1)],
+                kind: DifferenceKind::WireType { old: 3, new: 2 },
+            }]
+        );
+        assert_eq!(diff(b"\x0b\x10\x01\x0c", b"\x0b\x10\x01\x0c"), vec![]);
+    }
+
+    #[test]
+    fn field_key_display_works() {
+        assert_eq!(key(3).to_string(), ".3");
+        assert_eq!(
+            FieldKey {
+                number: 3,
+                index: Some(0)
+            }
+            .to_string(),
+            ".3[0]"
+        );
+    }
+}
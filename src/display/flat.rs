@@ -0,0 +1,129 @@
+//! Flat output with one `path = value` line per value for grep and awk.
+
+use std::collections::HashMap;
+
+use super::formatter::Formatter;
+use super::wire_type_2::{show_as, ShowAs, ShowAsConfig};
+use super::yaml::{print_fixed32, print_fixed64};
+use crate::filter::SelectQuery;
+use crate::tree::{field_indices, Field, FieldValue, Message};
+
+/// Formats messages as one line per value like `.3[1].2 = "Mac Smith"`.
+///
+/// Nested messages have no line of their own, only empty ones are shown as `{}`.
+/// Fields occurring more than once in a message get their position as index, so every
+/// path can be passed back as select query. Values are shown in full and without colors.
+/// Strings are double-quoted, binary data is hex. Fixed-width values are unsigned
+/// integers with the other readings as comment.
+#[derive(Clone, Default)]
+pub struct FlatFormatter {
+    /// Only the fields below this path are shown. Paths are always shown in full.
+    pub select: SelectQuery,
+}
+
+impl Formatter for FlatFormatter {
+    fn format(&self, message: &Message) -> String {
+        let mut out = String::new();
+        self.write_fields(&message.fields, "", 0, &mut out);
+        out
+    }
+}
+
+impl FlatFormatter {
+    /// Writes the fields of a message at the given path and depth
+    fn write_fields(&self, fields: &[Field], path: &str, depth: usize, out: &mut String) {
+        let mut counts = HashMap::<u64, usize>::new();
+        for field in fields {
+            *counts.entry(field.number).or_default() += 1;
+        }
+        for (field, index) in fields.iter().zip(field_indices(fields)) {
+            if depth < self.select.len()
+                && (field.number != self.select[depth] || !self.select.matches_index(depth, index))
+            {
+                continue;
+            }
+            let repeated = counts[&field.number] > 1;
+            let field_path = if repeated {
+                format!("{}.{}[{}]", path, field.number, index)
+            } else {
+                format!("{}.{}", path, field.number)
+            };
+            match &field.value {
+                FieldValue::Message(message) if !message.fields.is_empty() => {
+                    self.write_fields(&message.fields, &field_path, depth + 1, out)
+                }
+                // Values above the selected path
+                _ if depth + 1 < self.select.len() => {}
+                value => {
+                    out.push_str(&format!("{} = {}\n", field_path, print_value(value)));
+                }
+            }
+        }
+    }
+}
+
+fn print_value(value: &FieldValue) -> String {
+    match value {
+        FieldValue::Varint(v) => v.to_string(),
+        FieldValue::Fixed64(v) => print_fixed64(*v),
+        FieldValue::Fixed32(v) => print_fixed32(*v),
+        FieldValue::Bytes(v) => match show_as(v, &ShowAsConfig::default()) {
+            ShowAs::String(s) => serde_json::to_string(s).unwrap(),
+            _ => hex::encode(v),
+        },
+        FieldValue::Message(_) => "{}".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse::ParseConfig;
+
+    // 1: 150, 2 { 1: "a\nb" }, 2 { 1: 0001ff, 2 { 3: fixed32, 4 {} } }
+    const MESSAGE: &[u8] = b"\x08\x96\x01\x12\x05\x0a\x03a\nb\
+                             \x12\x0e\x0a\x03\x00\x01\xff\x12\x07\x1d\x00\x00\x80\x3f\x22\x00";
+
+    fn format(select: &str, bytes: &[u8]) -> String {
+        let formatter = FlatFormatter {
+            select: SelectQuery::parse(select).unwrap(),
+        };
+        formatter.format(&Message::decode(bytes, ParseConfig::default()).unwrap())
+    }
+
+    #[test]
+    fn flat_formatter_works() {
+        assert_eq!(
+            format("", MESSAGE),
+            ".1 = 150\n\
+             .2[0].1 = \"a\\nb\"\n\
+             .2[1].1 = 0001ff\n\
+             .2[1].2.3 = 1065353216 # float 1.0\n\
+             .2[1].2.4 = \"\"\n"
+        );
+    }
+
+    #[test]
+    fn flat_formatter_paths_can_be_selected() {
+        for line in format("", MESSAGE).lines() {
+            let (path, _) = line.split_once(" = ").unwrap();
+            assert_eq!(format(path, MESSAGE), format!("{}\n", line));
+        }
+        assert_eq!(
+            format(".2[1].2", MESSAGE),
+            ".2[1].2.3 = 1065353216 # float 1.0\n\
+             .2[1].2.4 = \"\"\n"
+        );
+        assert_eq!(format(".2.1", MESSAGE).lines().count(), 2);
+        assert_eq!(format(".1.1", MESSAGE), "");
+    }
+
+    #[test]
+    fn flat_formatter_handles_many_repeated_fields() {
+        // Indexing the fields must not take quadratic time in their number
+        let bytes = b"\x08\x01".repeat(100_000);
+        let out = format("", &bytes);
+        assert_eq!(out.lines().count(), 100_000);
+        assert_eq!(out.lines().last(), Some(".1[99999] = 1"));
+    }
+}
@@ -0,0 +1,8 @@
+//! The extension point for rendering decoded messages.
+
+use crate::tree::Message;
+
+/// Renders a decoded message, e.g. as text for a terminal.
+pub trait Formatter {
+    fn format(&self, message: &Message) -> String;
+}
@@ -0,0 +1,63 @@
+//! An xxd-like hexdump with annotations.
+
+/// Number of bytes shown in one row
+const BYTES_PER_ROW: usize = 16;
+
+/// Formats the bytes found at the given offset as hexdump rows.
+///
+/// Each row contains the offset, up to 16 bytes as hex, the printable ASCII
+/// characters and, in the first row only, the annotation.
+pub fn hexdump_rows(offset: usize, bytes: &[u8], annotation: &str) -> Vec<String> {
+    bytes
+        .chunks(BYTES_PER_ROW)
+        .enumerate()
+        .map(|(i, chunk)| {
+            let hex: Vec<String> = chunk.iter().map(|b| format!("{:02x}", b)).collect();
+            let ascii: String = chunk
+                .iter()
+                .map(|&b| match b {
+                    0x20..=0x7e => b as char,
+                    _ => '.',
+                })
+                .collect();
+            let row = format!(
+                "{:08x}: {:<width$}  {:<ascii_width$}",
+                offset + i * BYTES_PER_ROW,
+                hex.join(" "),
+                ascii,
+                width = BYTES_PER_ROW * 3 - 1,
+                ascii_width = BYTES_PER_ROW
+            );
+            if i == 0 && !annotation.is_empty() {
+                format!("{}  {}", row, annotation)
+            } else {
+                row.trim_end().to_string()
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hexdump_rows_works() {
+        assert_eq!(hexdump_rows(0, b"", "nothing"), Vec::<String>::new());
+        assert_eq!(
+            hexdump_rows(0, b"\x08", ".1 tag"),
+            &["00000000: 08                                               .                 .1 tag"]
+        );
+        assert_eq!(
+            hexdump_rows(0x1a, b"\x96\x01", ""),
+            &["0000001a: 96 01                                            .."]
+        );
+        assert_eq!(
+            hexdump_rows(5, b"0123456789abcdefXYZ", ".2 = string"),
+            &[
+                "00000005: 30 31 32 33 34 35 36 37 38 39 61 62 63 64 65 66  0123456789abcdef  .2 = string",
+                "00000015: 58 59 5a                                         XYZ",
+            ]
+        );
+    }
+}
@@ -0,0 +1,281 @@
+//! A self-contained HTML page for attaching decoded messages to tickets.
+
+use std::ops::Range;
+
+use super::formatter::Formatter;
+use super::text::TextFormatter;
+use super::yaml::selected_fields;
+use crate::tree::{Field, FieldValue, Message};
+
+const STYLE: &str = "\
+body { background: #1e1e1e; color: #d4d4d4; font-family: monospace; }
+summary { cursor: pointer; }
+.value { white-space: pre-wrap; }
+.nested { margin-left: 2ch; border-left: 1px dotted #808080; padding-left: 1ch; }
+.value[title] { text-decoration: underline dotted; cursor: help; }
+.hex { color: #808080; margin-left: 2ch; }
+.red { color: #f14c4c; }
+.green { color: #23d18b; }
+.yellow { color: #f5f543; }
+.purple { color: #d670d6; }
+.cyan { color: #29b8db; }
+.bold { font-weight: bold; }
+.dimmed { opacity: 0.6; }
+";
+
+/// Formats messages as HTML page with the values and colors of a [`TextFormatter`].
+///
+/// Nested messages are collapsible. Varint and fixed-width values show all their
+/// readings as tooltip. Each field is followed by its bytes in hex if `input` is the
+/// decoded input.
+#[derive(Clone, Default)]
+pub struct HtmlFormatter<'a> {
+    /// Formats the values and selects the fields
+    pub text: TextFormatter,
+    pub input: &'a [u8],
+}
+
+impl Formatter for HtmlFormatter<'_> {
+    fn format(&self, message: &Message) -> String {
+        let mut out = String::new();
+        out.push_str("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n");
+        out.push_str("<title>decode_raw</title>\n");
+        out.push_str(&format!("<style>\n{}</style>\n", STYLE));
+        out.push_str("</head>\n<body>\n");
+        let fields = selected_fields(&message.fields, &self.text.select, 0);
+        self.write_fields(&fields, &self.text.select, &mut out);
+        out.push_str("</body>\n</html>\n");
+        out
+    }
+}
+
+impl HtmlFormatter<'_> {
+    fn write_fields(&self, fields: &[&Field], path: &[u64], out: &mut String) {
+        for field in fields {
+            let field_path = [path, &[field.number]].concat();
+            let span = &field.span;
+            match &field.value {
+                FieldValue::Message(message) => {
+                    out.push_str(&format!(
+                        "<details open><summary>{} {{{}</summary><div class=\"nested\">",
+                        field.number,
+                        self.print_hex(span.start..span.payload.start)
+                    ));
+                    let nested: Vec<&Field> = message.fields.iter().collect();
+                    self.write_fields(&nested, &field_path, out);
+                    out.push_str(&format!(
+                        "</div>}}{}</details>",
+                        self.print_hex(span.payload.end..span.end())
+                    ));
+                }
+                value => {
+                    let text = self.text.format_value(value, &field_path);
+                    let title = match readings(value) {
+                        Some(readings) => {
+                            let lines: Vec<String> = readings.iter().map(|r| escape(r)).collect();
+                            format!(" title=\"{}\"", lines.join("&#10;"))
+                        }
+                        None => String::new(),
+                    };
+                    out.push_str(&format!(
+                        "<div>{}: <span class=\"value\"{}>{}</span>{}</div>",
+                        field.number,
+                        title,
+                        ansi_to_html(&text),
+                        self.print_hex(span.start..span.end())
+                    ));
+                }
+            }
+            out.push('\n');
+        }
+    }
+
+    /// The bytes in the range of the input, e.g. a whole field or only the tag and
+    /// length prefix of a nested message
+    fn print_hex(&self, range: Range<usize>) -> String {
+        let start = range.start;
+        match self.input.get(range) {
+            Some([]) | None => String::new(),
+            Some(bytes) => {
+                let hex: Vec<String> = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+                format!(
+                    "<span class=\"hex\" title=\"@{}\">{}</span>",
+                    start,
+                    hex.join(" ")
+                )
+            }
+        }
+    }
+}
+
+/// All readings of a varint or fixed-width value
+fn readings(value: &FieldValue) -> Option<Vec<String>> {
+    let out = match value {
+        FieldValue::Varint(v) => {
+            let mut out = vec![format!("uint64: {}", v)];
+            if let Ok(v) = u64::try_from(*v) {
+                if v > i64::MAX as u64 {
+                    out.push(format!("int64: {}", v as i64));
+                }
+                let zigzag = ((v >> 1) as i64) ^ -((v & 1) as i64);
+                out.push(format!("sint64: {}", zigzag));
+            }
+            if *v <= 1 {
+                out.push(format!("bool: {}", *v == 1));
+            }
+            out
+        }
+        FieldValue::Fixed64(v) => vec![
+            format!("fixed64: {}", u64::from_le_bytes(*v)),
+            format!("sfixed64: {}", i64::from_le_bytes(*v)),
+            format!("double: {:?}", f64::from_le_bytes(*v)),
+        ],
+        FieldValue::Fixed32(v) => vec![
+            format!("fixed32: {}", u32::from_le_bytes(*v)),
+            format!("sfixed32: {}", i32::from_le_bytes(*v)),
+            format!("float: {:?}", f32::from_le_bytes(*v)),
+        ],
+        FieldValue::Bytes(_) | FieldValue::Message(_) => return None,
+    };
+    Some(out)
+}
+
+/// Replaces the ANSI color codes written by `ansi_term` with spans of CSS classes
+fn ansi_to_html(text: &str) -> String {
+    let mut out = String::new();
+    let mut open = false;
+    let mut rest = text;
+    while let Some(start) = rest.find("\x1b[") {
+        out.push_str(&escape(&rest[..start]));
+        let Some(len) = rest[start..].find('m') else {
+            rest = &rest[start..];
+            break;
+        };
+        let classes: Vec<&str> = rest[start + 2..start + len]
+            .split(';')
+            .filter_map(|code| match code {
+                "1" => Some("bold"),
+                "2" => Some("dimmed"),
+                "31" => Some("red"),
+                "32" => Some("green"),
+                "33" => Some("yellow"),
+                "35" => Some("purple"),
+                "36" => Some("cyan"),
+                _ => None,
+            })
+            .collect();
+        if open {
+            out.push_str("</span>");
+            open = false;
+        }
+        if !classes.is_empty() {
+            out.push_str(&format!("<span class=\"{}\">", classes.join(" ")));
+            open = true;
+        }
+        rest = &rest[start + len + 1..];
+    }
+    out.push_str(&escape(rest));
+    if open {
+        out.push_str("</span>");
+    }
+    out
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::filter::SelectQuery;
+    use crate::parse::ParseConfig;
+
+    /// The lines of the body
+    fn format(formatter: &HtmlFormatter, bytes: &[u8]) -> Vec<String> {
+        let message = Message::decode(bytes, ParseConfig::default()).unwrap();
+        let page = formatter.format(&message);
+        let body = page.split_once("<body>\n").unwrap().1;
+        let body = body.strip_suffix("</body>\n</html>\n").unwrap();
+        body.lines().map(|line| line.to_string()).collect()
+    }
+
+    #[test]
+    fn html_formatter_works() {
+        // 1: 1, 2 { 3: "<a>" }, 4: fixed32
+        let bytes = b"\x08\x01\x12\x05\x1a\x03<a>\x25\x00\x00\x80\x3f";
+        let formatter = HtmlFormatter {
+            input: bytes,
+            ..Default::default()
+        };
+        assert_eq!(
+            format(&formatter, bytes),
+            vec![
+                "<div>1: <span class=\"value\" title=\"uint64: 1&#10;sint64: -1&#10;bool: true\">\
+                 <span class=\"red\">1</span></span>\
+                 <span class=\"hex\" title=\"@0\">08 01</span></div>",
+                "<details open><summary>2 {<span class=\"hex\" title=\"@2\">12 05</span>\
+                 </summary><div class=\"nested\">\
+                 <div>3: <span class=\"value\">(3 bytes) \
+                 <span class=\"green\">'&lt;a&gt;'</span></span>\
+                 <span class=\"hex\" title=\"@4\">1a 03 3c 61 3e</span></div>",
+                "</div>}</details>",
+                "<div>4: <span class=\"value\" \
+                 title=\"fixed32: 1065353216&#10;sfixed32: 1065353216&#10;float: 1.0\">(32 bit) \
+                 <span class=\"yellow\">1065353216</span> / \
+                 <span class=\"yellow\">1</span></span>\
+                 <span class=\"hex\" title=\"@9\">25 00 00 80 3f</span></div>",
+            ]
+        );
+    }
+
+    #[test]
+    fn html_formatter_shows_end_group_tags() {
+        // 1 (group) { 2: 1 }
+        let bytes = b"\x0b\x10\x01\x0c";
+        let formatter = HtmlFormatter {
+            input: bytes,
+            ..Default::default()
+        };
+        let body = format(&formatter, bytes);
+        assert!(body[0].contains("<span class=\"hex\" title=\"@0\">0b</span>"));
+        assert_eq!(
+            body[1],
+            "</div>}<span class=\"hex\" title=\"@3\">0c</span></details>"
+        );
+    }
+
+    #[test]
+    fn html_formatter_selects() {
+        // 1 { 2: 1 }, 3: 2
+        let bytes = b"\x0a\x02\x10\x01\x18\x02";
+        let formatter = HtmlFormatter {
+            text: TextFormatter {
+                select: SelectQuery::parse(".1").unwrap(),
+                ..Default::default()
+            },
+            input: &[],
+        };
+        let body = format(&formatter, bytes);
+        assert_eq!(body.len(), 1);
+        assert!(body[0].starts_with("<div>2: "));
+        // Without input there are no bytes
+        assert!(!body[0].contains("class=\"hex\""));
+    }
+
+    #[test]
+    fn ansi_to_html_works() {
+        assert_eq!(ansi_to_html("a < b"), "a &lt; b");
+        assert_eq!(
+            ansi_to_html("\x1b[31m1\x1b[0m / \x1b[1;31m[x]\x1b[0m"),
+            "<span class=\"red\">1</span> / <span class=\"bold red\">[x]</span>"
+        );
+        assert_eq!(
+            ansi_to_html("\x1b[2mnote"),
+            "<span class=\"dimmed\">note</span>"
+        );
+    }
+}
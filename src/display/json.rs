@@ -0,0 +1,136 @@
+//! Pretty-printing of JSON documents embedded in string fields.
+
+use ansi_term::Colour::{Blue, Green, Purple, Yellow};
+use serde_json::Value;
+
+/// Parses the string as JSON document and returns it pretty-printed and colorized,
+/// one line per element.
+///
+/// Only objects and arrays are considered documents. Strings like `123` or `true`
+/// are valid JSON too but are better displayed as plain strings.
+pub fn pretty_json(input: &str) -> Option<Vec<String>> {
+    let trimmed = input.trim_start();
+    if !trimmed.starts_with('{') && !trimmed.starts_with('[') {
+        return None;
+    }
+    let value: Value = serde_json::from_str(input).ok()?;
+    let mut out = String::new();
+    write_value(&value, 0, &mut out);
+    Some(out.lines().map(|line| line.to_string()).collect())
+}
+
+fn write_value(value: &Value, level: usize, out: &mut String) {
+    match value {
+        Value::Null => out.push_str(&Purple.paint("null").to_string()),
+        Value::Bool(b) => out.push_str(&Purple.paint(b.to_string()).to_string()),
+        Value::Number(n) => out.push_str(&Yellow.paint(n.to_string()).to_string()),
+        Value::String(s) => out.push_str(&Green.paint(quote(s)).to_string()),
+        Value::Array(elements) => {
+            if elements.is_empty() {
+                out.push_str("[]");
+                return;
+            }
+            out.push_str("[\n");
+            for (i, element) in elements.iter().enumerate() {
+                indent(level + 1, out);
+                write_value(element, level + 1, out);
+                if i + 1 != elements.len() {
+                    out.push(',');
+                }
+                out.push('\n');
+            }
+            indent(level, out);
+            out.push(']');
+        }
+        Value::Object(members) => {
+            if members.is_empty() {
+                out.push_str("{}");
+                return;
+            }
+            out.push_str("{\n");
+            for (i, (key, member)) in members.iter().enumerate() {
+                indent(level + 1, out);
+                out.push_str(&Blue.paint(quote(key)).to_string());
+                out.push_str(": ");
+                write_value(member, level + 1, out);
+                if i + 1 != members.len() {
+                    out.push(',');
+                }
+                out.push('\n');
+            }
+            indent(level, out);
+            out.push('}');
+        }
+    }
+}
+
+fn quote(s: &str) -> String {
+    serde_json::to_string(s).unwrap()
+}
+
+fn indent(level: usize, out: &mut String) {
+    for _ in 0..level {
+        out.push_str("  ");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::display::strip_colors;
+
+    fn pretty_json_plain(input: &str) -> Option<Vec<String>> {
+        pretty_json(input).map(|lines| lines.iter().map(|l| strip_colors(l)).collect())
+    }
+
+    #[test]
+    fn pretty_json_works() {
+        assert_eq!(
+            pretty_json_plain(r#"{"my":"json"}"#).unwrap(),
+            &["{", r#"  "my": "json""#, "}"]
+        );
+        assert_eq!(
+            pretty_json_plain(r#"{"b":[1,2.5,true,null],"a":{},"c":[]}"#).unwrap(),
+            &[
+                "{",
+                r#"  "b": ["#,
+                "    1,",
+                "    2.5,",
+                "    true,",
+                "    null",
+                "  ],",
+                r#"  "a": {},"#,
+                r#"  "c": []"#,
+                "}"
+            ]
+        );
+        // Large numbers are not rounded
+        assert_eq!(
+            pretty_json_plain(r#"[123456789012345678901234567890]"#).unwrap(),
+            &["[", "  123456789012345678901234567890", "]"]
+        );
+        // Strings are escaped
+        assert_eq!(
+            pretty_json_plain(r#"["a\"b\n"]"#).unwrap(),
+            &["[", r#"  "a\"b\n""#, "]"]
+        );
+    }
+
+    #[test]
+    fn pretty_json_colorizes() {
+        assert_eq!(
+            pretty_json(r#"{"a":1}"#).unwrap()[1],
+            format!("  {}: {}", Blue.paint(r#""a""#), Yellow.paint("1"))
+        );
+    }
+
+    #[test]
+    fn pretty_json_ignores_non_documents() {
+        assert_eq!(pretty_json(""), None);
+        assert_eq!(pretty_json("123"), None);
+        assert_eq!(pretty_json("true"), None);
+        assert_eq!(pretty_json(r#""foo""#), None);
+        assert_eq!(pretty_json("{not json}"), None);
+        assert_eq!(pretty_json(r#"{"a":1} trailing"#), None);
+    }
+}
@@ -1,5 +1,25 @@
+mod flat;
+mod formatter;
+mod hexdump;
+mod html;
 mod indent;
+mod json;
+mod text;
+mod truncate;
+mod well_known;
 mod wire_type_2;
+mod yaml;
 
+pub use flat::FlatFormatter;
+pub use formatter::Formatter;
+pub use hexdump::hexdump_rows;
+pub use html::HtmlFormatter;
 pub use indent::{dotted, spaced};
-pub use wire_type_2::{escape_string, show_as, ShowAs};
+pub use json::pretty_json;
+pub use text::{IndentStyle, Limits, TextFormatter};
+pub use truncate::{truncate_bytes, truncate_str};
+pub use well_known::{detect_timestamp, format_timestamp};
+pub use wire_type_2::{
+    bytes_kinds, escape_string, show_as, strip_colors, BytesKind, ShowAs, ShowAsConfig,
+};
+pub use yaml::YamlFormatter;
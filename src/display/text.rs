@@ -0,0 +1,756 @@
+//! The colored text format of the command line tool.
+
+use std::str::FromStr;
+
+use ansi_term::Colour::{Cyan, Green, Purple, Red, Yellow};
+use ansi_term::Style;
+
+use super::formatter::Formatter;
+use super::indent::{dotted, spaced};
+use super::json::pretty_json;
+use super::truncate::{truncate_bytes, truncate_str};
+use super::well_known::{detect_timestamp, format_timestamp};
+use super::wire_type_2::{bytes_kinds, escape_string, show_as, BytesKind, ShowAs, ShowAsConfig};
+use crate::filter::SelectQuery;
+use crate::parse::{
+    is_likely_message, is_valid_field_number, read_fields, wire_type_name, FieldSpan, ParseConfig,
+};
+use crate::tree::{Field, FieldValue, Message};
+
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Debug, Default)]
+pub enum IndentStyle {
+    Space,
+    #[default]
+    Dot,
+    Path,
+}
+
+impl FromStr for IndentStyle {
+    type Err = String;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        match input {
+            "space" => Ok(IndentStyle::Space),
+            "dot" => Ok(IndentStyle::Dot),
+            "path" => Ok(IndentStyle::Path),
+            _ => Err(format!("unknown indent style '{}'", input)),
+        }
+    }
+}
+
+/// Maximum lengths of values to show. `None` means unlimited.
+#[derive(Clone, Default)]
+pub struct Limits {
+    pub max_bytes: Option<usize>,
+    pub max_string: Option<usize>,
+    pub keep_tail: bool,
+    pub overrides: Vec<(SelectQuery, Option<usize>)>,
+}
+
+impl Limits {
+    /// The maximum lengths of bytes and strings at the given path.
+    /// The override with the longest matching path wins.
+    pub fn at(&self, path: &[u64]) -> (Option<usize>, Option<usize>) {
+        self.overrides
+            .iter()
+            .filter(|(query, _)| path.starts_with(query))
+            .max_by_key(|(query, _)| query.len())
+            .map(|(_, max)| (*max, *max))
+            .unwrap_or((self.max_bytes, self.max_string))
+    }
+}
+
+/// Formats messages as colored text with one line per field.
+#[derive(Clone, Default)]
+pub struct TextFormatter {
+    pub indent: IndentStyle,
+    /// Only the fields below this path are shown. Their paths are shown relative to it.
+    pub select: SelectQuery,
+    pub limits: Limits,
+    pub detect_timestamps: bool,
+    pub pretty_json: bool,
+    pub show_as_config: ShowAsConfig,
+    pub offsets: bool,
+    pub show_wire_types: bool,
+    /// Show the reading as message of bytes that were rejected as nested message
+    /// and the confidence of nested messages that are not certain
+    pub show_alternatives: bool,
+    /// Used to decode messages in base64 and hex encoded strings
+    pub parse_config: ParseConfig,
+}
+
+impl Formatter for TextFormatter {
+    fn format(&self, message: &Message) -> String {
+        let mut out = String::new();
+        self.write_fields(&message.fields, &[], &mut out);
+        out
+    }
+}
+
+impl TextFormatter {
+    /// Formats a single value like in a field line. Nested messages are abbreviated.
+    pub fn format_value(&self, value: &FieldValue, path: &[u64]) -> String {
+        match value {
+            FieldValue::Fixed64(v) => format!("(64 bit) {}", print_fixed64(*v)),
+            FieldValue::Fixed32(v) => format!("(32 bit) {}", print_fixed32(*v)),
+            FieldValue::Varint(i) => print_int(*i),
+            FieldValue::Bytes(v) => {
+                let shown = show_as(v, &self.show_as_config);
+                format!(
+                    "({}) {}",
+                    print_length(v.len(), shown.encoding()),
+                    self.print_bytes(shown, path)
+                )
+            }
+            FieldValue::Message(_) => "{…}".to_string(),
+        }
+    }
+
+    /// Writes the fields of a message found at the given path
+    fn write_fields(&self, fields: &[Field], path: &[u64], out: &mut String) {
+        if path.len() >= self.select.len() {
+            for field in fields {
+                self.write_field(field, &[path, &[field.number]].concat(), out);
+            }
+            return;
+        }
+        for field in self.select.select_fields(fields, path.len()) {
+            let field_path = [path, &[field.number]].concat();
+            match &field.value {
+                FieldValue::Message(message) => {
+                    self.write_fields(&message.fields, &field_path, out)
+                }
+                // The selected field itself is shown without path
+                _ if field_path.len() == self.select.len() => {
+                    self.write_field(field, &field_path, out)
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn write_field(&self, field: &Field, path: &[u64], out: &mut String) {
+        let stripped_path = &path[self.select.len()..];
+        let path_text = self.print_path(stripped_path);
+        let location = self.print_annotations(field);
+        match &field.value {
+            FieldValue::Fixed64(v) => line(
+                out,
+                format!("{}: (64 bit) {}{}", path_text, print_fixed64(*v), location),
+            ),
+            FieldValue::Fixed32(v) => line(
+                out,
+                format!("{}: (32 bit) {}{}", path_text, print_fixed32(*v), location),
+            ),
+            FieldValue::Varint(i) => {
+                line(out, format!("{}: {}{}", path_text, print_int(*i), location))
+            }
+            FieldValue::Bytes(v) => {
+                let group = field.span.wire_type == 3;
+                self.write_bytes(v, group, path, &path_text, &location, out);
+                if self.show_alternatives && field.confidence.is_some() {
+                    self.write_alternative(field, v, path, &path_text, out);
+                }
+            }
+            FieldValue::Message(message) => {
+                let timestamp = if self.detect_timestamps {
+                    nested_varints(message).and_then(|f| detect_timestamp(&f))
+                } else {
+                    None
+                };
+                // Marks messages that must be encoded with start and end group tags
+                let group = if field.span.wire_type == 3 {
+                    " (group)"
+                } else {
+                    ""
+                };
+                match timestamp {
+                    Some((seconds, nanos)) => line(
+                        out,
+                        format!(
+                            "{} {{{} (timestamp) {}{}",
+                            path_text,
+                            group,
+                            Purple.paint(format_timestamp(seconds, nanos)),
+                            location
+                        ),
+                    ),
+                    None => line(out, format!("{} {{{}{}", path_text, group, location)),
+                }
+                self.write_fields(&message.fields, path, out);
+                line(out, format!("{}}}", close_indent(&path_text)));
+            }
+        }
+    }
+
+    /// Writes a length-delimited field or the payload of a group that is not shown as
+    /// nested message
+    fn write_bytes(
+        &self,
+        v: &[u8],
+        group: bool,
+        path: &[u64],
+        path_text: &str,
+        location: &str,
+        out: &mut String,
+    ) {
+        let stripped_path = &path[self.select.len()..];
+        let shown = show_as(v, &self.show_as_config);
+        if group {
+            line(
+                out,
+                format!(
+                    "{}: ({}) {}{}",
+                    path_text,
+                    print_length(v.len(), Some("group")),
+                    self.print_bytes(ShowAs::Bytes(v), path),
+                    location
+                ),
+            );
+            return;
+        }
+        if self.pretty_json {
+            if let ShowAs::String(s) = shown {
+                if let Some(lines) = pretty_json(s) {
+                    line(
+                        out,
+                        format!(
+                            "{}: ({} bytes) {}{}",
+                            path_text,
+                            v.len(),
+                            lines[0],
+                            location
+                        ),
+                    );
+                    let indent = self.print_indent(stripped_path);
+                    for json_line in &lines[1..] {
+                        line(out, format!("{}{}", indent, json_line));
+                    }
+                    return;
+                }
+            }
+        }
+        if let ShowAs::Base64(_, decoded) | ShowAs::Hex(_, decoded) = &shown {
+            // Strings are subject to the same checks as payloads nested in the input
+            let likely = read_fields(decoded)
+                .is_ok_and(|fields| is_likely_message(&fields, self.parse_config));
+            let nested = Message::decode(decoded, self.parse_config).filter(|_| likely);
+            if let Some(nested) = nested {
+                line(
+                    out,
+                    format!(
+                        "{} {{ ({}){}",
+                        path_text,
+                        shown.encoding().unwrap(),
+                        location
+                    ),
+                );
+                // Locations are relative to the decoded string, not the input
+                let nested_formatter = TextFormatter {
+                    offsets: false,
+                    ..self.clone()
+                };
+                nested_formatter.write_fields(&nested.fields, path, out);
+                line(out, format!("{}}}", close_indent(path_text)));
+                return;
+            }
+        }
+        line(
+            out,
+            format!(
+                "{}: ({}) {}{}",
+                path_text,
+                print_length(v.len(), shown.encoding()),
+                self.print_bytes(shown, path),
+                location
+            ),
+        )
+    }
+
+    /// Writes the reading as message of bytes that were rejected as nested message
+    /// because of their low confidence. The lines are commented out with `#` since
+    /// they show the same field again.
+    fn write_alternative(
+        &self,
+        field: &Field,
+        v: &[u8],
+        path: &[u64],
+        path_text: &str,
+        out: &mut String,
+    ) {
+        // The payload itself is not subject to the confidence, only fields nested in it
+        let Some(message) = Message::decode(v, self.parse_config) else {
+            return;
+        };
+        let text = format!(
+            "[alternative: message with confidence {:.2}]",
+            field.confidence.unwrap_or_default()
+        );
+        let mut block = String::new();
+        line(
+            &mut block,
+            format!("{} {{  {}", path_text, Style::new().dimmed().paint(text)),
+        );
+        // Locations are relative to the payload, not the input
+        let nested_formatter = TextFormatter {
+            offsets: false,
+            ..self.clone()
+        };
+        nested_formatter.write_fields(&message.fields, path, &mut block);
+        line(&mut block, format!("{}}}", close_indent(path_text)));
+        for block_line in block.lines() {
+            line(
+                out,
+                format!("{} {}", Style::new().dimmed().paint("#"), block_line),
+            );
+        }
+    }
+
+    /// Formats the wire level details of a field that are enabled, field numbers
+    /// protobuf does not allow and the limit that stopped decoding for appending them
+    /// to an output line
+    fn print_annotations(&self, field: &Field) -> String {
+        let span = &field.span;
+        let mut out = String::new();
+        if !is_valid_field_number(field.number) {
+            out.push_str(&format!("  {}", Red.paint("[invalid field number]")));
+        }
+        if let Some(limit) = field.limit {
+            let text = format!("[{}]", limit);
+            out.push_str(&format!("  {}", Style::new().dimmed().paint(text)));
+        }
+        if let (true, FieldValue::Message(_), Some(confidence)) =
+            (self.show_alternatives, &field.value, field.confidence)
+        {
+            if confidence < 1.0 {
+                let text = format!("[confidence {:.2}]", confidence);
+                out.push_str(&format!("  {}", Style::new().dimmed().paint(text)));
+            }
+        }
+        if self.show_wire_types {
+            let text = format!(
+                "[wire type {}: {}]",
+                span.wire_type,
+                wire_type_name(span.wire_type)
+            );
+            out.push_str(&format!("  {}", Style::new().dimmed().paint(text)));
+            if !span.overlong.is_empty() {
+                out.push_str(&format!(" {}", print_overlong(span)));
+            }
+        }
+        if self.offsets {
+            out.push_str(&format!("  {}", print_location(span)));
+        }
+        out
+    }
+
+    fn print_bytes(&self, shown: ShowAs, path: &[u64]) -> String {
+        let keep_tail = self.limits.keep_tail;
+        let (max_bytes, max_string) = self.limits.at(path);
+        match shown {
+            ShowAs::String(s) | ShowAs::Base64(s, _) | ShowAs::Hex(s, _) => {
+                let text = match max_string.and_then(|max| truncate_str(s, max, keep_tail)) {
+                    Some((head, "")) => format!("{}…", escape_string(head)),
+                    Some((head, tail)) => {
+                        format!("{}…{}", escape_string(head), escape_string(tail))
+                    }
+                    None => escape_string(s),
+                };
+                Green.paint(text).to_string()
+            }
+            ShowAs::Bytes(bytes) => {
+                let text = match max_bytes.and_then(|max| truncate_bytes(bytes, max, keep_tail)) {
+                    Some((head, tail)) => format!("{}…{}", hex::encode(head), hex::encode(tail)),
+                    None => hex::encode(bytes),
+                };
+                let mut out = Green.paint(text).to_string();
+                for kind in bytes_kinds(bytes, &self.show_as_config) {
+                    out.push_str(&format!(" ({})", print_bytes_kind(kind)));
+                }
+                out
+            }
+        }
+    }
+
+    /// The prefix for continuation lines of the field at the given path
+    /// such that they align with the start of the path.
+    fn print_indent(&self, path: &[u64]) -> String {
+        match self.indent {
+            IndentStyle::Dot => dotted(path.len().saturating_sub(1)),
+            IndentStyle::Space => spaced(path.len().saturating_sub(1)),
+            IndentStyle::Path => String::new(),
+        }
+    }
+
+    fn print_path(&self, path: &[u64]) -> String {
+        match self.indent {
+            IndentStyle::Dot => {
+                let mut out = dotted(path.len().saturating_sub(1));
+                if let Some(last) = path.last() {
+                    out.push_str(&format!("{}", last));
+                }
+                out
+            }
+            IndentStyle::Space => {
+                let mut out = spaced(path.len().saturating_sub(1));
+                if let Some(last) = path.last() {
+                    out.push_str(&format!("{}", last));
+                }
+                out
+            }
+            IndentStyle::Path => {
+                let formated_path: String =
+                    path.iter().map(|number| format!(".{}", number)).collect();
+                formated_path
+            }
+        }
+    }
+}
+
+fn line(out: &mut String, text: String) {
+    out.push_str(&text);
+    out.push('\n');
+}
+
+/// The indent of the line closing a nested message opened in a line starting with the path
+fn close_indent(path_text: &str) -> String {
+    dotted(path_text.chars().count().saturating_sub(1) / 2)
+}
+
+/// Returns the fields of the message as (field number, value) pairs if all of them are varints
+fn nested_varints(message: &Message) -> Option<Vec<(u64, u128)>> {
+    message
+        .fields
+        .iter()
+        .map(|field| match field.value {
+            FieldValue::Varint(v) => Some((field.number, v)),
+            _ => None,
+        })
+        .collect()
+}
+
+fn print_overlong(span: &FieldSpan) -> String {
+    let parts: Vec<&str> = span.overlong.iter().map(|part| part.name()).collect();
+    let text = format!("[non-minimal {}]", parts.join(", "));
+    Red.bold().paint(text).to_string()
+}
+
+/// Formats the location of a field in the input
+fn print_location(span: &FieldSpan) -> String {
+    let mut parts = vec![format!("tag {}", span.tag_len)];
+    if span.length_len != 0 {
+        parts.push(format!("length {}", span.length_len));
+    }
+    parts.push(format!(
+        "payload {}..{}",
+        span.payload.start, span.payload.end
+    ));
+    if span.end_tag_len != 0 {
+        parts.push(format!("end tag {}", span.end_tag_len));
+    }
+    let text = format!("@{} ({})", span.start, parts.join(", "));
+    Style::new().dimmed().paint(text).to_string()
+}
+
+fn yellow(s: impl ToString) -> String {
+    Yellow.paint(s.to_string()).to_string()
+}
+
+fn print_fixed64(v: [u8; 8]) -> String {
+    let as_unsigned = u64::from_le_bytes(v);
+    let as_signed = i64::from_le_bytes(v);
+    let as_float = f64::from_le_bytes(v);
+
+    let mut values = Vec::<String>::new();
+    values.push(yellow(as_unsigned));
+    if as_signed < 0 {
+        values.push(yellow(as_signed));
+    }
+    values.push(yellow(as_float));
+    values.join(" / ")
+}
+
+fn print_fixed32(v: [u8; 4]) -> String {
+    let as_unsigned = u32::from_le_bytes(v);
+    let as_signed = i32::from_le_bytes(v);
+    let as_float = f32::from_le_bytes(v);
+
+    let mut values = Vec::<String>::new();
+    values.push(yellow(as_unsigned));
+    if as_signed < 0 {
+        values.push(yellow(as_signed));
+    }
+    values.push(yellow(as_float));
+    values.join(" / ")
+}
+
+fn print_int(i: impl Into<u128>) -> String {
+    Red.paint(i.into().to_string()).to_string()
+}
+
+fn print_length(len: usize, encoding: Option<&str>) -> String {
+    match encoding {
+        Some(encoding) => format!("{} bytes, {}", len, encoding),
+        None => format!("{} bytes", len),
+    }
+}
+
+fn print_bytes_kind(kind: BytesKind) -> String {
+    match kind {
+        BytesKind::Hash => "hash".to_string(),
+        BytesKind::Uuid(uuid) => format!("uuid {}", Cyan.paint(uuid)),
+        BytesKind::Address(address) => format!("address {}", Cyan.paint(address)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::display::strip_colors;
+
+    // 1: 150, 2: "John", 3 { 1: 1, 4: fixed32 -1 }
+    const MESSAGE: &[u8] = b"\x08\x96\x01\x12\x04John\x1a\x07\x08\x01\x25\xff\xff\xff\xff";
+
+    fn format(formatter: &TextFormatter, bytes: &[u8]) -> String {
+        let message = Message::decode(bytes, ParseConfig::default()).unwrap();
+        strip_colors(&formatter.format(&message))
+    }
+
+    #[test]
+    fn text_formatter_works() {
+        assert_eq!(
+            format(&TextFormatter::default(), MESSAGE),
+            "1: 150\n\
+             2: (4 bytes) \"John\"\n\
+             3 {\n\
+             · 1: 1\n\
+             · 4: (32 bit) 4294967295 / -1 / NaN\n\
+             }\n"
+        );
+    }
+
+    #[test]
+    fn text_formatter_shows_parse_limits() {
+        // 1 { 2 { 3: 1 } }
+        let bytes = b"\x0a\x04\x12\x02\x18\x01";
+        let config = ParseConfig {
+            max_depth: 1,
+            ..Default::default()
+        };
+        let message = Message::decode(bytes, config).unwrap();
+        assert_eq!(
+            strip_colors(&TextFormatter::default().format(&message)),
+            "1 {\n\
+             · 2: (2 bytes) 1801  [not decoded, max depth reached]\n\
+             }\n"
+        );
+    }
+
+    #[test]
+    fn text_formatter_flags_invalid_field_numbers() {
+        // 0: 1, 536870912: 1, 536870911: 1
+        let bytes = b"\x00\x01\x80\x80\x80\x80\x10\x01\xf8\xff\xff\xff\x0f\x01";
+        assert_eq!(
+            format(&TextFormatter::default(), bytes),
+            "0: 1  [invalid field number]\n\
+             536870912: 1  [invalid field number]\n\
+             536870911: 1\n"
+        );
+    }
+
+    #[test]
+    fn text_formatter_marks_groups() {
+        // 1 (group) { 2 (group) { 3: 1 } }
+        let bytes = b"\x0b\x13\x18\x01\x14\x0c";
+        assert_eq!(
+            format(&TextFormatter::default(), bytes),
+            "1 { (group)\n\
+             · 2 { (group)\n\
+             · · 3: 1\n\
+             · }\n\
+             }\n"
+        );
+        let config = ParseConfig {
+            max_depth: 1,
+            ..Default::default()
+        };
+        let message = Message::decode(bytes, config).unwrap();
+        assert_eq!(
+            strip_colors(&TextFormatter::default().format(&message)),
+            "1 { (group)\n\
+             · 2: (2 bytes, group) 1801  [not decoded, max depth reached]\n\
+             }\n"
+        );
+    }
+
+    #[test]
+    fn text_formatter_shows_alternatives() {
+        // 1 { 2: 1, 1: 1 }
+        let bytes = b"\x0a\x04\x10\x01\x08\x01";
+        let config = ParseConfig {
+            min_confidence: 0.9,
+            ..Default::default()
+        };
+        let message = Message::decode(bytes, config).unwrap();
+        let formatter = TextFormatter {
+            show_alternatives: true,
+            parse_config: config,
+            ..Default::default()
+        };
+        assert_eq!(
+            strip_colors(&formatter.format(&message)),
+            "1: (4 bytes) 10010801\n\
+             # 1 {  [alternative: message with confidence 0.80]\n\
+             # · 2: 1\n\
+             # · 1: 1\n\
+             # }\n"
+        );
+
+        let message = Message::decode(bytes, ParseConfig::default()).unwrap();
+        assert_eq!(
+            strip_colors(&formatter.format(&message)),
+            "1 {  [confidence 0.80]\n\
+             · 2: 1\n\
+             · 1: 1\n\
+             }\n"
+        );
+    }
+
+    #[test]
+    fn text_formatter_checks_confidence_of_decoded_strings() {
+        let formatter = TextFormatter {
+            show_as_config: ShowAsConfig {
+                decode_strings: true,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        // 1: "0801100a1a03616263", i.e. 1: 1, 2: 10, 3: "abc" as hex
+        let bytes = b"\x0a\x120801100a1a03616263";
+        assert_eq!(
+            format(&formatter, bytes),
+            "1 { (hex)\n\
+             · 1: 1\n\
+             · 2: 10\n\
+             · 3: (3 bytes) \"abc\"\n\
+             }\n"
+        );
+        // A hash that is a valid serialization with descending field numbers
+        let hash = "30bef3ee80d750103175f44b825869b0d230cdc1adced509dbd600fcaa8abe0e";
+        let mut bytes = b"\x0a\x40".to_vec();
+        bytes.extend(hash.as_bytes());
+        assert_eq!(
+            format(&formatter, &bytes),
+            format!("1: (64 bytes, hex) \"{}\"\n", hash)
+        );
+    }
+
+    #[test]
+    fn text_formatter_selects_in_many_repeated_fields() {
+        // Selecting must not take quadratic time in the number of fields
+        let bytes = b"\x08\x01".repeat(100_000);
+        let formatter = TextFormatter {
+            select: SelectQuery::parse(".1[99999]").unwrap(),
+            ..Default::default()
+        };
+        assert_eq!(format(&formatter, &bytes), ": 1\n");
+    }
+
+    #[test]
+    fn text_formatter_supports_indent_styles() {
+        let formatter = TextFormatter {
+            indent: IndentStyle::Path,
+            ..Default::default()
+        };
+        assert_eq!(
+            format(&formatter, MESSAGE),
+            ".1: 150\n\
+             .2: (4 bytes) \"John\"\n\
+             .3 {\n\
+             .3.1: 1\n\
+             .3.4: (32 bit) 4294967295 / -1 / NaN\n\
+             }\n"
+        );
+        let formatter = TextFormatter {
+            indent: IndentStyle::Space,
+            ..Default::default()
+        };
+        assert!(format(&formatter, MESSAGE).contains("\n  1: 1\n"));
+    }
+
+    #[test]
+    fn text_formatter_selects() {
+        let formatter = TextFormatter {
+            select: SelectQuery::parse(".3").unwrap(),
+            ..Default::default()
+        };
+        assert_eq!(
+            format(&formatter, MESSAGE),
+            "1: 1\n\
+             4: (32 bit) 4294967295 / -1 / NaN\n"
+        );
+        // The selected value itself has no path
+        let formatter = TextFormatter {
+            select: SelectQuery::parse(".2").unwrap(),
+            ..Default::default()
+        };
+        assert_eq!(format(&formatter, MESSAGE), ": (4 bytes) \"John\"\n");
+        // One of the fields with the same number
+        let formatter = TextFormatter {
+            select: SelectQuery::parse(".3[1]").unwrap(),
+            ..Default::default()
+        };
+        // 3 { 1: 1 }, 3 { 1: 2 }
+        assert_eq!(
+            format(&formatter, b"\x1a\x02\x08\x01\x1a\x02\x08\x02"),
+            "1: 2\n"
+        );
+    }
+
+    #[test]
+    fn text_formatter_format_value_works() {
+        let formatter = TextFormatter {
+            limits: Limits {
+                max_bytes: Some(2),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        assert_eq!(
+            strip_colors(&formatter.format_value(&FieldValue::Varint(5), &[1])),
+            "5"
+        );
+        assert_eq!(
+            strip_colors(&formatter.format_value(&FieldValue::Bytes(&[0, 1, 2]), &[1])),
+            "(3 bytes) 0001…"
+        );
+        assert_eq!(
+            formatter.format_value(&FieldValue::Message(Message::default()), &[1]),
+            "{…}"
+        );
+    }
+
+    #[test]
+    fn indent_style_from_str_works() {
+        assert_eq!("dot".parse::<IndentStyle>(), Ok(IndentStyle::Dot));
+        assert_eq!("space".parse::<IndentStyle>(), Ok(IndentStyle::Space));
+        assert_eq!("path".parse::<IndentStyle>(), Ok(IndentStyle::Path));
+        assert!("tab".parse::<IndentStyle>().is_err());
+    }
+
+    #[test]
+    fn limits_at_works() {
+        let limits = Limits {
+            max_bytes: Some(10),
+            max_string: None,
+            keep_tail: false,
+            overrides: vec![
+                (SelectQuery::parse(".3").unwrap(), Some(5)),
+                (SelectQuery::parse(".3.2").unwrap(), None),
+            ],
+        };
+        assert_eq!(limits.at(&[1]), (Some(10), None));
+        assert_eq!(limits.at(&[3, 1]), (Some(5), Some(5)));
+        assert_eq!(limits.at(&[3, 2, 1]), (None, None));
+    }
+}
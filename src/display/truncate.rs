@@ -0,0 +1,90 @@
+//! Shortening of long values for display.
+
+/// Returns the head and tail parts to show if the bytes are longer than allowed.
+///
+/// Without `keep_tail`, the first `max` bytes are kept and the tail is empty.
+/// With `keep_tail`, the first and last `max` bytes are kept.
+/// Returns `None` if nothing needs to be removed.
+pub fn truncate_bytes(bytes: &[u8], max: usize, keep_tail: bool) -> Option<(&[u8], &[u8])> {
+    if keep_tail {
+        if bytes.len() <= max.saturating_mul(2) {
+            return None;
+        }
+        Some((&bytes[..max], &bytes[bytes.len() - max..]))
+    } else {
+        if bytes.len() <= max {
+            return None;
+        }
+        Some((&bytes[..max], &[]))
+    }
+}
+
+/// Like [`truncate_bytes`] but for strings. Here `max` is the number of characters.
+pub fn truncate_str(s: &str, max: usize, keep_tail: bool) -> Option<(&str, &str)> {
+    let char_count = s.chars().count();
+    if keep_tail {
+        if char_count <= max.saturating_mul(2) {
+            return None;
+        }
+        let head_end = char_offset(s, max);
+        let tail_start = char_offset(s, char_count - max);
+        Some((&s[..head_end], &s[tail_start..]))
+    } else {
+        if char_count <= max {
+            return None;
+        }
+        Some((&s[..char_offset(s, max)], ""))
+    }
+}
+
+/// The byte offset of the nth character
+fn char_offset(s: &str, n: usize) -> usize {
+    s.char_indices().nth(n).map(|(i, _)| i).unwrap_or(s.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn truncate_bytes_works() {
+        assert_eq!(truncate_bytes(b"", 3, false), None);
+        assert_eq!(truncate_bytes(b"abc", 3, false), None);
+        assert_eq!(
+            truncate_bytes(b"abcd", 3, false),
+            Some((&b"abc"[..], &b""[..]))
+        );
+        assert_eq!(
+            truncate_bytes(b"abcd", 0, false),
+            Some((&b""[..], &b""[..]))
+        );
+
+        // keep tail
+        assert_eq!(truncate_bytes(b"abcdef", 3, true), None);
+        assert_eq!(
+            truncate_bytes(b"abcdefg", 3, true),
+            Some((&b"abc"[..], &b"efg"[..]))
+        );
+        assert_eq!(
+            truncate_bytes(b"abcdefg", 1, true),
+            Some((&b"a"[..], &b"g"[..]))
+        );
+        assert_eq!(truncate_bytes(b"abc", usize::MAX, true), None);
+    }
+
+    #[test]
+    fn truncate_str_works() {
+        assert_eq!(truncate_str("", 3, false), None);
+        assert_eq!(truncate_str("abc", 3, false), None);
+        assert_eq!(truncate_str("abcd", 3, false), Some(("abc", "")));
+        // counts characters, not bytes
+        assert_eq!(truncate_str("äöü", 3, false), None);
+        assert_eq!(truncate_str("äöüß", 3, false), Some(("äöü", "")));
+
+        // keep tail
+        assert_eq!(truncate_str("abcdef", 3, true), None);
+        assert_eq!(truncate_str("abcdefg", 3, true), Some(("abc", "efg")));
+        assert_eq!(truncate_str("äöüßäöü", 2, true), Some(("äö", "öü")));
+        assert_eq!(truncate_str("abc", usize::MAX, true), None);
+    }
+}
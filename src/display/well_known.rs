@@ -0,0 +1,130 @@
+//! Rendering of protobuf well-known types such as `google.protobuf.Timestamp`.
+//!
+//! Only Timestamps are detected, by a heuristic. Durations, wrappers and `Struct`
+//! would need a schema to be told apart from other messages.
+
+/// Smallest seconds value we consider a plausible timestamp (2000-01-01T00:00:00Z).
+const PLAUSIBLE_SECONDS_MIN: u128 = 946684800;
+/// Largest seconds value we consider a plausible timestamp (2100-01-01T00:00:00Z).
+const PLAUSIBLE_SECONDS_MAX: u128 = 4102444800;
+
+/// Checks if the direct children of a nested message look like a `google.protobuf.Timestamp`.
+///
+/// The children are given as (field number, varint value) pairs. This expects field 1 (seconds)
+/// in a plausible range and an optional field 2 (nanos) that is a valid nanosecond value.
+/// Returns seconds and nanos if the message is a likely Timestamp.
+pub fn detect_timestamp(fields: &[(u64, u128)]) -> Option<(i64, u32)> {
+    let mut seconds = None;
+    let mut nanos = None;
+    for (number, value) in fields {
+        match number {
+            1 if seconds.is_none() => seconds = Some(*value),
+            2 if nanos.is_none() => nanos = Some(*value),
+            _ => return None,
+        }
+    }
+    let seconds = seconds?;
+    let nanos = nanos.unwrap_or_default();
+    if !(PLAUSIBLE_SECONDS_MIN..PLAUSIBLE_SECONDS_MAX).contains(&seconds) || nanos >= 1_000_000_000
+    {
+        return None;
+    }
+    Some((seconds as i64, nanos as u32))
+}
+
+/// Formats a Timestamp as RFC 3339 string in UTC.
+///
+/// Like the protobuf JSON mapping, the fractional part uses 0, 3, 6 or 9 digits.
+pub fn format_timestamp(seconds: i64, nanos: u32) -> String {
+    let days = seconds.div_euclid(86400);
+    let second_of_day = seconds.rem_euclid(86400);
+    let (year, month, day) = civil_from_days(days);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}{}Z",
+        year,
+        month,
+        day,
+        second_of_day / 3600,
+        (second_of_day % 3600) / 60,
+        second_of_day % 60,
+        format_nanos(nanos)
+    )
+}
+
+fn format_nanos(nanos: u32) -> String {
+    if nanos == 0 {
+        String::new()
+    } else if nanos % 1_000_000 == 0 {
+        format!(".{:03}", nanos / 1_000_000)
+    } else if nanos % 1_000 == 0 {
+        format!(".{:06}", nanos / 1_000)
+    } else {
+        format!(".{:09}", nanos)
+    }
+}
+
+/// Converts days since 1970-01-01 to a (year, month, day) triple in the proleptic
+/// Gregorian calendar.
+///
+/// See http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097); // [0, 146096]
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365; // [0, 399]
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100); // [0, 365]
+    let mp = (5 * doy + 2) / 153; // [0, 11]
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32; // [1, 31]
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32; // [1, 12]
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detect_timestamp_works() {
+        assert_eq!(
+            detect_timestamp(&[(1, 1643277740), (2, 123000000)]),
+            Some((1643277740, 123000000))
+        );
+        // nanos are optional
+        assert_eq!(detect_timestamp(&[(1, 1643277740)]), Some((1643277740, 0)));
+
+        // seconds required
+        assert_eq!(detect_timestamp(&[]), None);
+        assert_eq!(detect_timestamp(&[(2, 123000000)]), None);
+        // seconds out of range
+        assert_eq!(detect_timestamp(&[(1, 1021211)]), None);
+        assert_eq!(detect_timestamp(&[(1, 95941545151)]), None);
+        // nanos out of range
+        assert_eq!(detect_timestamp(&[(1, 1643277740), (2, 1000000000)]), None);
+        // other fields
+        assert_eq!(detect_timestamp(&[(1, 1643277740), (3, 7)]), None);
+        assert_eq!(detect_timestamp(&[(1, 1643277740), (1, 1643277740)]), None);
+    }
+
+    #[test]
+    fn format_timestamp_works() {
+        assert_eq!(format_timestamp(0, 0), "1970-01-01T00:00:00Z");
+        assert_eq!(format_timestamp(1643277740, 0), "2022-01-27T10:02:20Z");
+        assert_eq!(format_timestamp(951782400, 0), "2000-02-29T00:00:00Z");
+        assert_eq!(format_timestamp(-1, 0), "1969-12-31T23:59:59Z");
+
+        // fractional seconds
+        assert_eq!(
+            format_timestamp(1643277740, 100000000),
+            "2022-01-27T10:02:20.100Z"
+        );
+        assert_eq!(
+            format_timestamp(1643277740, 123456000),
+            "2022-01-27T10:02:20.123456Z"
+        );
+        assert_eq!(
+            format_timestamp(1643277740, 123456789),
+            "2022-01-27T10:02:20.123456789Z"
+        );
+    }
+}
@@ -1,6 +1,8 @@
 //! Everything about wire type 2 (Length-delimited),
 //! i.e. string, bytes, embedded messages, packed repeated fields.
 
+use base64::Engine;
+
 pub fn escape_string(input: &str) -> String {
     let escaped = snailquote::escape(input);
     if !escaped.starts_with('"') && !escaped.starts_with('\'') {
@@ -10,13 +12,70 @@ pub fn escape_string(input: &str) -> String {
     }
 }
 
+/// Removes the ANSI color codes of the colored output
+pub fn strip_colors(text: &str) -> String {
+    let mut out = String::new();
+    let mut in_escape = false;
+    for c in text.chars() {
+        match (in_escape, c) {
+            (false, '\x1b') => in_escape = true,
+            (false, c) => out.push(c),
+            (true, 'm') => in_escape = false,
+            (true, _) => {}
+        }
+    }
+    out
+}
+
 #[derive(Debug, PartialEq)]
 pub enum ShowAs<'a> {
     String(&'a str),
     Bytes(&'a [u8]),
+    /// A string that contains base64 encoded data (standard alphabet with padding)
+    Base64(&'a str, Vec<u8>),
+    /// A string that contains hex encoded data, either all lowercase or all uppercase
+    Hex(&'a str, Vec<u8>),
 }
 
-pub fn show_as<'a>(bytes: &'a [u8]) -> ShowAs<'a> {
+impl ShowAs<'_> {
+    /// The name of the detected encoding if this is an encoded string.
+    /// Uppercase hex is called `HEX`.
+    pub fn encoding(&self) -> Option<&'static str> {
+        match self {
+            ShowAs::Base64(..) => Some("base64"),
+            ShowAs::Hex(s, _) if s.bytes().any(|b| b.is_ascii_uppercase()) => Some("HEX"),
+            ShowAs::Hex(..) => Some("hex"),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Default)]
+pub struct ShowAsConfig {
+    /// Detect base64 and hex encoded strings
+    pub decode_strings: bool,
+    /// Detect hashes and UUIDs in binary data
+    pub detect_bytes: bool,
+    /// Show 20 and 32 byte values as bech32 addresses with this prefix
+    pub bech32_prefix: Option<bech32::Hrp>,
+}
+
+/// What binary data might represent.
+#[derive(Debug, PartialEq)]
+pub enum BytesKind {
+    /// A 32 byte hash like sha256
+    Hash,
+    /// An RFC 4122 UUID in its canonical string representation
+    Uuid(String),
+    /// A bech32 address
+    Address(String),
+}
+
+/// The minimum length of a string to be considered base64 or hex encoded.
+/// Shorter strings are too likely to be regular text.
+const MIN_ENCODED_LEN: usize = 8;
+
+pub fn show_as<'a>(bytes: &'a [u8], config: &ShowAsConfig) -> ShowAs<'a> {
     match std::str::from_utf8(bytes) {
         Ok(converted) => {
             if converted.chars().all(|char| match char {
@@ -24,6 +83,14 @@ pub fn show_as<'a>(bytes: &'a [u8]) -> ShowAs<'a> {
                 '\0'..='\x19' => false,     // Non-printable ASCII characters
                 _ => true,
             }) {
+                if config.decode_strings {
+                    if let Some(decoded) = decode_hex(converted) {
+                        return ShowAs::Hex(converted, decoded);
+                    }
+                    if let Some(decoded) = decode_base64(converted) {
+                        return ShowAs::Base64(converted, decoded);
+                    }
+                }
                 ShowAs::String(converted)
             } else {
                 ShowAs::Bytes(bytes)
@@ -33,6 +100,80 @@ pub fn show_as<'a>(bytes: &'a [u8]) -> ShowAs<'a> {
     }
 }
 
+/// The maximum length of a bech32 string according to BIP 173
+const BECH32_MAX_LENGTH: usize = 90;
+
+/// Detects what the given binary data might represent. Multiple kinds
+/// can apply to the same data, e.g. 32 bytes can be a hash or an address.
+pub fn bytes_kinds(bytes: &[u8], config: &ShowAsConfig) -> Vec<BytesKind> {
+    let mut out = Vec::new();
+    if config.detect_bytes {
+        if bytes.len() == 32 {
+            out.push(BytesKind::Hash);
+        }
+        if bytes.len() == 16 && is_uuid(bytes) {
+            out.push(BytesKind::Uuid(format_uuid(bytes)));
+        }
+    }
+    if let Some(prefix) = config.bech32_prefix {
+        if bytes.len() == 20 || bytes.len() == 32 {
+            match bech32::encode::<bech32::Bech32>(prefix, bytes) {
+                Ok(address) if address.len() <= BECH32_MAX_LENGTH => {
+                    out.push(BytesKind::Address(address))
+                }
+                // Long prefixes do not leave room for the data
+                _ => {}
+            }
+        }
+    }
+    out
+}
+
+/// Checks for the RFC 4122 variant and a known version (1-8)
+fn is_uuid(bytes: &[u8]) -> bool {
+    let version = bytes[6] >> 4;
+    let variant = bytes[8] >> 6;
+    (1..=8).contains(&version) && variant == 0b10
+}
+
+fn format_uuid(bytes: &[u8]) -> String {
+    format!(
+        "{}-{}-{}-{}-{}",
+        hex::encode(&bytes[0..4]),
+        hex::encode(&bytes[4..6]),
+        hex::encode(&bytes[6..8]),
+        hex::encode(&bytes[8..10]),
+        hex::encode(&bytes[10..16])
+    )
+}
+
+/// Decodes hex strings with consistent case. At least one of the letters a-f must be
+/// contained to avoid interpreting decimal numbers as hex.
+fn decode_hex(input: &str) -> Option<Vec<u8>> {
+    if input.len() < MIN_ENCODED_LEN || !input.bytes().any(|b| b.is_ascii_alphabetic()) {
+        return None;
+    }
+    let lower = input
+        .bytes()
+        .all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f'));
+    let upper = input
+        .bytes()
+        .all(|b| matches!(b, b'0'..=b'9' | b'A'..=b'F'));
+    if !lower && !upper {
+        return None;
+    }
+    hex::decode(input).ok()
+}
+
+/// Decodes base64 strings. Strings consisting of letters only are ignored
+/// since they are most likely words.
+fn decode_base64(input: &str) -> Option<Vec<u8>> {
+    if input.len() < MIN_ENCODED_LEN || input.bytes().all(|b| b.is_ascii_alphabetic()) {
+        return None;
+    }
+    base64::engine::general_purpose::STANDARD.decode(input).ok()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -59,8 +200,18 @@ mod tests {
         );
     }
 
+    #[test]
+    fn strip_colors_works() {
+        assert_eq!(
+            strip_colors("\x1b[31m150\x1b[0m / \x1b[1;2mx\x1b[0m"),
+            "150 / x"
+        );
+        assert_eq!(strip_colors("plain"), "plain");
+    }
+
     #[test]
     fn show_as_works() {
+        let show_as = |bytes| super::show_as(bytes, &ShowAsConfig::default());
         assert_eq!(show_as(b""), ShowAs::String(""));
         assert_eq!(show_as(b"123"), ShowAs::String("123"));
         assert_eq!(show_as(b"with space"), ShowAs::String("with space"));
@@ -76,5 +227,122 @@ mod tests {
         assert_eq!(show_as(b"__\0__"), ShowAs::Bytes(b"__\0__")); // Null
         assert_eq!(show_as(b"__\x07__"), ShowAs::Bytes(b"__\x07__")); // Bell
         assert_eq!(show_as(b"__\x0b__"), ShowAs::Bytes(b"__\x0b__")); // Vertical Tab
+
+        // Encoded strings are only detected when enabled
+        assert_eq!(show_as(b"0a0b0c0d0e"), ShowAs::String("0a0b0c0d0e"));
+        assert_eq!(
+            show_as(b"CgsKCQoHTm9ib2R5"),
+            ShowAs::String("CgsKCQoHTm9ib2R5")
+        );
+    }
+
+    #[test]
+    fn show_as_detects_encodings() {
+        let config = ShowAsConfig {
+            decode_strings: true,
+            ..ShowAsConfig::default()
+        };
+
+        // hex
+        assert_eq!(
+            show_as(b"0a0b0c0d0e", &config),
+            ShowAs::Hex("0a0b0c0d0e", vec![10, 11, 12, 13, 14])
+        );
+        assert_eq!(
+            show_as(b"0A0B0C0D0E", &config),
+            ShowAs::Hex("0A0B0C0D0E", vec![10, 11, 12, 13, 14])
+        );
+        assert_eq!(show_as(b"0a0b0c0d0e", &config).encoding(), Some("hex"));
+        assert_eq!(show_as(b"0A0B0C0D0E", &config).encoding(), Some("HEX"));
+        // mixed case is not hex
+        assert_eq!(show_as(b"0a0B0c0D0e0F", &config).encoding(), Some("base64"));
+        assert_eq!(show_as(b"0a0b", &config), ShowAs::String("0a0b"));
+        assert_eq!(show_as(b"0a0b0c0d0", &config), ShowAs::String("0a0b0c0d0"));
+        assert_eq!(
+            show_as(b"1234567890", &config),
+            ShowAs::String("1234567890")
+        );
+
+        // base64
+        assert_eq!(
+            show_as(b"CgZOb2JvZHk=", &config),
+            ShowAs::Base64("CgZOb2JvZHk=", b"\n\x06Nobody".to_vec())
+        );
+        assert_eq!(
+            show_as(b"SGVsbG8=", &config),
+            ShowAs::Base64("SGVsbG8=", b"Hello".to_vec())
+        );
+        assert_eq!(show_as(b"password", &config), ShowAs::String("password"));
+        assert_eq!(show_as(b"SGVsbG8", &config), ShowAs::String("SGVsbG8"));
+        assert_eq!(
+            show_as(b"with space", &config),
+            ShowAs::String("with space")
+        );
+
+        // Binary data is never an encoded string
+        let non_utf8 = vec![0, 159, 146, 150];
+        assert_eq!(show_as(&non_utf8, &config), ShowAs::Bytes(&non_utf8));
+    }
+
+    #[test]
+    fn bytes_kinds_works() {
+        let uuid = hex::decode("550e8400e29b41d4a716446655440000").unwrap();
+        let hash = hex::decode("e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855")
+            .unwrap();
+        let address = hex::decode("0cf95e3fb1ef8c4cbd1cab7f8f1d3dc18a1df2e5").unwrap();
+
+        // Nothing detected by default
+        let config = ShowAsConfig::default();
+        assert_eq!(bytes_kinds(&uuid, &config), vec![]);
+        assert_eq!(bytes_kinds(&hash, &config), vec![]);
+        assert_eq!(bytes_kinds(&address, &config), vec![]);
+
+        let config = ShowAsConfig {
+            detect_bytes: true,
+            ..ShowAsConfig::default()
+        };
+        assert_eq!(
+            bytes_kinds(&uuid, &config),
+            vec![BytesKind::Uuid(
+                "550e8400-e29b-41d4-a716-446655440000".to_string()
+            )]
+        );
+        assert_eq!(bytes_kinds(&hash, &config), vec![BytesKind::Hash]);
+        assert_eq!(bytes_kinds(&address, &config), vec![]);
+        // Wrong variant
+        let non_uuid = hex::decode("550e8400e29b41d4c716446655440000").unwrap();
+        assert_eq!(bytes_kinds(&non_uuid, &config), vec![]);
+        // Wrong version
+        let non_uuid = hex::decode("550e8400e29b01d4a716446655440000").unwrap();
+        assert_eq!(bytes_kinds(&non_uuid, &config), vec![]);
+
+        let config = ShowAsConfig {
+            detect_bytes: true,
+            bech32_prefix: Some(bech32::Hrp::parse("cosmos").unwrap()),
+            ..ShowAsConfig::default()
+        };
+        assert_eq!(
+            bytes_kinds(&address, &config),
+            vec![BytesKind::Address(
+                "cosmos1pnu4u0a3a7xye0gu4dlc78facx9pmuh9pcn4wz".to_string()
+            )]
+        );
+        assert_eq!(
+            bytes_kinds(&hash, &config),
+            vec![
+                BytesKind::Hash,
+                BytesKind::Address(
+                    "cosmos1uwcvgs5clswpfxhm7nyfjmaeysn6us0yvjdexn9yjkv3k7zjhp2sdphjup".to_string()
+                )
+            ]
+        );
+
+        // Too long for a 32 byte address
+        let config = ShowAsConfig {
+            bech32_prefix: Some(bech32::Hrp::parse(&"a".repeat(40)).unwrap()),
+            ..ShowAsConfig::default()
+        };
+        assert_eq!(bytes_kinds(&address, &config).len(), 1);
+        assert_eq!(bytes_kinds(&hash, &config), vec![]);
     }
 }
@@ -0,0 +1,227 @@
+//! YAML output for pasting into bug reports and tests.
+
+use std::collections::HashMap;
+
+use base64::Engine;
+
+use super::formatter::Formatter;
+use super::wire_type_2::{show_as, ShowAs, ShowAsConfig};
+use crate::filter::SelectQuery;
+use crate::tree::{Field, FieldValue, Message};
+
+/// Formats messages as YAML document with field numbers as keys.
+///
+/// Fields occurring more than once become sequences. Values are always shown in full
+/// and without colors. Strings are double-quoted, binary data is tagged `!!binary`.
+/// Fixed-width values are unsigned integers with the other readings as comment.
+#[derive(Clone, Default)]
+pub struct YamlFormatter {
+    /// Only the fields below this path are shown, like in [`TextFormatter`](super::TextFormatter)
+    pub select: SelectQuery,
+}
+
+impl Formatter for YamlFormatter {
+    fn format(&self, message: &Message) -> String {
+        let fields = selected_fields(&message.fields, &self.select, 0);
+        if fields.is_empty() {
+            return "{}\n".to_string();
+        }
+        let mut out = String::new();
+        write_mapping(&fields, 0, &mut out);
+        out
+    }
+}
+
+/// The fields of all messages at the selected path, or the selected fields themselves
+/// if they are not messages
+pub(super) fn selected_fields<'m, 'a>(
+    fields: &'m [Field<'a>],
+    select: &SelectQuery,
+    depth: usize,
+) -> Vec<&'m Field<'a>> {
+    if depth == select.len() {
+        return fields.iter().collect();
+    }
+    let mut out = Vec::new();
+    for field in select.select_fields(fields, depth) {
+        match &field.value {
+            FieldValue::Message(message) => {
+                out.extend(selected_fields(&message.fields, select, depth + 1))
+            }
+            _ if depth + 1 == select.len() => out.push(field),
+            _ => {}
+        }
+    }
+    out
+}
+
+/// Writes one key per field number in the order of first occurrence.
+/// Every line starts with the indent.
+fn write_mapping(fields: &[&Field], indent: usize, out: &mut String) {
+    // The fields grouped by number with the position of each number's group
+    let mut groups: Vec<Vec<&Field>> = Vec::new();
+    let mut positions = HashMap::new();
+    for &field in fields {
+        let position = *positions.entry(field.number).or_insert_with(|| {
+            groups.push(Vec::new());
+            groups.len() - 1
+        });
+        groups[position].push(field);
+    }
+    for values in groups {
+        let number = values[0].number;
+        let key = format!("{}{}:", " ".repeat(indent), number);
+        if let [value] = values[..] {
+            write_value(&key, value, indent, out);
+        } else {
+            out.push_str(&key);
+            out.push('\n');
+            let item = format!("{}-", " ".repeat(indent + 2));
+            for value in values {
+                write_value(&item, value, indent + 2, out);
+            }
+        }
+    }
+}
+
+/// Writes the value after the key or sequence item prefix, which is indented by `indent`
+fn write_value(prefix: &str, field: &Field, indent: usize, out: &mut String) {
+    match &field.value {
+        FieldValue::Message(message) if !message.fields.is_empty() => {
+            let fields: Vec<&Field> = message.fields.iter().collect();
+            let mut nested = String::new();
+            write_mapping(&fields, indent + 2, &mut nested);
+            if prefix.ends_with('-') {
+                // The first key goes on the line of the item
+                out.push_str(prefix);
+                out.push(' ');
+                out.push_str(&nested[indent + 2..]);
+            } else {
+                out.push_str(prefix);
+                out.push('\n');
+                out.push_str(&nested);
+            }
+        }
+        value => {
+            out.push_str(prefix);
+            out.push(' ');
+            out.push_str(&print_scalar(value));
+            out.push('\n');
+        }
+    }
+}
+
+fn print_scalar(value: &FieldValue) -> String {
+    match value {
+        FieldValue::Varint(v) => v.to_string(),
+        FieldValue::Fixed64(v) => print_fixed64(*v),
+        FieldValue::Fixed32(v) => print_fixed32(*v),
+        FieldValue::Bytes(v) => match show_as(v, &ShowAsConfig::default()) {
+            // A JSON string is a valid double-quoted YAML scalar
+            ShowAs::String(s) => serde_json::to_string(s).unwrap(),
+            _ => format!(
+                "!!binary {}",
+                base64::engine::general_purpose::STANDARD.encode(v)
+            ),
+        },
+        FieldValue::Message(_) => "{}".to_string(),
+    }
+}
+
+/// The unsigned value followed by the signed and float readings as comment
+pub(super) fn print_fixed64(v: [u8; 8]) -> String {
+    let mut readings = Vec::new();
+    if i64::from_le_bytes(v) < 0 {
+        readings.push(format!("sfixed64 {}", i64::from_le_bytes(v)));
+    }
+    readings.push(format!("double {:?}", f64::from_le_bytes(v)));
+    format!("{} # {}", u64::from_le_bytes(v), readings.join(", "))
+}
+
+/// The unsigned value followed by the signed and float readings as comment
+pub(super) fn print_fixed32(v: [u8; 4]) -> String {
+    let mut readings = Vec::new();
+    if i32::from_le_bytes(v) < 0 {
+        readings.push(format!("sfixed32 {}", i32::from_le_bytes(v)));
+    }
+    readings.push(format!("float {:?}", f32::from_le_bytes(v)));
+    format!("{} # {}", u32::from_le_bytes(v), readings.join(", "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse::ParseConfig;
+
+    fn format(formatter: &YamlFormatter, bytes: &[u8]) -> String {
+        formatter.format(&Message::decode(bytes, ParseConfig::default()).unwrap())
+    }
+
+    #[test]
+    fn yaml_formatter_works() {
+        // 1: 150, 2: "Jo\"e", 3 { 1: 1, 2 {} }, 3 { 1: 2 }, 4: 0001ff, 5: fixed64, 6: fixed32
+        let bytes = b"\x08\x96\x01\x12\x04Jo\"e\x1a\x04\x08\x01\x12\x00\x1a\x02\x08\x02\
+                      \x22\x03\x00\x01\xff\x29\xff\xff\xff\xff\xff\xff\xff\xff\
+                      \x35\x00\x00\x80\x3f";
+        assert_eq!(
+            format(&YamlFormatter::default(), bytes),
+            "1: 150\n\
+             2: \"Jo\\\"e\"\n\
+             3:\n\
+            \x20 - 1: 1\n\
+            \x20   2: \"\"\n\
+            \x20 - 1: 2\n\
+             4: !!binary AAH/\n\
+             5: 18446744073709551615 # sfixed64 -1, double NaN\n\
+             6: 1065353216 # float 1.0\n"
+        );
+    }
+
+    #[test]
+    fn yaml_formatter_nests_messages() {
+        // 1 { 2 { 3: 1, 3: 2 } }
+        let bytes = b"\x0a\x06\x12\x04\x18\x01\x18\x02";
+        assert_eq!(
+            format(&YamlFormatter::default(), bytes),
+            "1:\n\
+            \x20 2:\n\
+            \x20   3:\n\
+            \x20     - 1\n\
+            \x20     - 2\n"
+        );
+    }
+
+    #[test]
+    fn yaml_formatter_selects() {
+        // 1 { 2 { 3: 1, 3: 2 } }, 4: 5
+        let bytes = b"\x0a\x06\x12\x04\x18\x01\x18\x02\x20\x05";
+        let formatter = |path| YamlFormatter {
+            select: SelectQuery::parse(path).unwrap(),
+        };
+        assert_eq!(format(&formatter(".1.2"), bytes), "3:\n  - 1\n  - 2\n");
+        assert_eq!(format(&formatter(".4"), bytes), "4: 5\n");
+        assert_eq!(format(&formatter(".5"), bytes), "{}\n");
+        // 1: 1, 1: 2
+        let bytes = b"\x08\x01\x08\x02";
+        assert_eq!(format(&formatter(".1[1]"), bytes), "1: 2\n");
+        assert_eq!(format(&formatter(".1[2]"), bytes), "{}\n");
+    }
+
+    #[test]
+    fn yaml_formatter_groups_many_distinct_fields() {
+        // Grouping must not take quadratic time in the number of fields
+        // 1: 0, 2: 0, ..., 60000: 0, 1: 0
+        let mut bytes = Vec::new();
+        for number in (1..=60_000u64).chain([1]) {
+            let mut tag = number << 3;
+            while tag >= 0x80 {
+                bytes.push(tag as u8 | 0x80);
+                tag >>= 7;
+            }
+            bytes.extend([tag as u8, 0]);
+        }
+        let out = format(&YamlFormatter::default(), &bytes);
+        assert!(out.starts_with("1:\n  - 0\n  - 0\n2: 0\n"));
+        assert!(out.ends_with("60000: 0\n"));
+    }
+}
@@ -0,0 +1,358 @@
+//! Changing single fields of a serialized message without knowing its schema.
+
+use decode_raw::{scan_fields, FieldSpan};
+
+use super::wire::write_varint;
+
+/// A change to the fields at a path
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Edit<'a> {
+    /// Replaces the value of all fields at the path. Missing fields and
+    /// messages are created.
+    Set(&'a str),
+    /// Removes all fields at the path
+    Delete,
+}
+
+/// Applies the edit to the fields at the path and returns the new serialization.
+///
+/// Everything else is copied byte by byte. Only the length prefixes of the
+/// messages containing the changed fields are recomputed. Groups keep their
+/// start and end tags and can only be changed by the fields inside them.
+///
+/// Existing fields keep their wire type and the value is parsed accordingly:
+/// a number for varints, a number or float for 64 and 32 bit fields and
+/// the UTF-8 bytes of the value for length-delimited fields. New fields are
+/// varints if the value is an integer and length-delimited otherwise.
+///
+/// Fields are only created if the path exists nowhere in the message. They are
+/// added to the message at the longest part of the path that exists, which must
+/// be unique.
+pub fn edit_fields(bytes: &[u8], path: &[u64], edit: Edit) -> Result<Vec<u8>, String> {
+    if path.is_empty() {
+        return Err("the path must not be empty".to_string());
+    }
+    let mut out = Vec::new();
+    let mut changed = edit_message(bytes, path, edit, None, &mut out)?;
+    if changed == 0 && matches!(edit, Edit::Set(_)) {
+        let (depth, count) = existing_depth(bytes, path);
+        if count > 1 {
+            return Err(format!(
+                "{} exists {} times, so it is unclear where to create {}",
+                print_path(&path[..depth]),
+                count,
+                print_path(&path[depth..])
+            ));
+        }
+        out.clear();
+        changed = edit_message(bytes, path, edit, Some(depth), &mut out)?;
+    }
+    if changed == 0 {
+        return Err(format!("no field found at {}", print_path(path)));
+    }
+    Ok(out)
+}
+
+/// Writes the edited message and returns the number of changed fields.
+///
+/// Missing fields are only created if `create` is set. It is the number of
+/// elements of the path that exist below this message as returned by `existing_depth`.
+fn edit_message(
+    bytes: &[u8],
+    path: &[u64],
+    edit: Edit,
+    create: Option<usize>,
+    out: &mut Vec<u8>,
+) -> Result<usize, String> {
+    let spans = scan_fields(bytes);
+    if spans.last().map(|span| span.end()).unwrap_or(0) != bytes.len() {
+        return Err("not a valid protobuf serialization".to_string());
+    }
+    let number = path[0];
+    let mut changed = 0;
+    for span in &spans {
+        if span.number != number {
+            out.extend_from_slice(&bytes[span.start..span.end()]);
+            continue;
+        }
+        let tag = &bytes[span.start..span.start + span.tag_len];
+        let payload = &bytes[span.payload.clone()];
+        if path.len() > 1 {
+            if span.wire_type != 2 && span.wire_type != 3 {
+                return Err(format!("field {} is not a message", number));
+            }
+            // Only the message with the longest existing part of the path is changed
+            let nested_create = match create {
+                Some(depth) if depth > 0 && existing_depth(payload, &path[1..]).0 == depth - 1 => {
+                    Some(depth - 1)
+                }
+                Some(_) => {
+                    out.extend_from_slice(&bytes[span.start..span.end()]);
+                    continue;
+                }
+                None => None,
+            };
+            let mut nested = Vec::new();
+            changed += edit_message(payload, &path[1..], edit, nested_create, &mut nested)
+                .map_err(|err| format!("field {}: {}", number, err))?;
+            if span.wire_type == 3 {
+                out.extend_from_slice(tag);
+                out.extend_from_slice(&nested);
+                out.extend_from_slice(&bytes[span.payload.end..span.end()]);
+            } else {
+                write_length_delimited(tag, &nested, out);
+            }
+        } else {
+            changed += 1;
+            if let Edit::Set(value) = edit {
+                write_value(span, tag, value, out)?;
+            }
+        }
+    }
+
+    if changed == 0 && create == Some(0) {
+        if let Edit::Set(value) = edit {
+            let mut tag = Vec::new();
+            if path.len() > 1 {
+                write_varint(((number as u128) << 3) | 2, &mut tag);
+                let mut nested = Vec::new();
+                changed += edit_message(&[], &path[1..], edit, Some(0), &mut nested)?;
+                write_length_delimited(&tag, &nested, out);
+            } else {
+                match parse_integer(value) {
+                    Some(v) => {
+                        write_varint((number as u128) << 3, out);
+                        write_varint(v as u128, out);
+                    }
+                    None => {
+                        write_varint(((number as u128) << 3) | 2, &mut tag);
+                        write_length_delimited(&tag, value.as_bytes(), out);
+                    }
+                }
+                changed += 1;
+            }
+        }
+    }
+    Ok(changed)
+}
+
+/// Returns how many elements at the start of the path exist in the message and
+/// in how many places this longest part of the path occurs
+fn existing_depth(bytes: &[u8], path: &[u64]) -> (usize, usize) {
+    let mut deepest = (0, 1);
+    for span in scan_fields(bytes) {
+        if span.number != path[0] {
+            continue;
+        }
+        let (depth, count) = match span.wire_type {
+            2 | 3 if path.len() > 1 => existing_depth(&bytes[span.payload.clone()], &path[1..]),
+            _ => (0, 1),
+        };
+        if depth + 1 > deepest.0 {
+            deepest = (depth + 1, count);
+        } else if depth + 1 == deepest.0 {
+            deepest.1 += count;
+        }
+    }
+    deepest
+}
+
+/// Writes the field with its original tag and the new value
+fn write_value(span: &FieldSpan, tag: &[u8], value: &str, out: &mut Vec<u8>) -> Result<(), String> {
+    let invalid = |kind: &str| {
+        format!(
+            "'{}' is not a valid {} for field {}",
+            value, kind, span.number
+        )
+    };
+    match span.wire_type {
+        0 => {
+            let v = parse_integer(value).ok_or_else(|| invalid("varint"))?;
+            out.extend_from_slice(tag);
+            write_varint(v as u128, out);
+        }
+        1 => {
+            let v = parse_integer(value)
+                .or_else(|| value.parse::<f64>().ok().map(f64::to_bits))
+                .ok_or_else(|| invalid("64 bit value"))?;
+            out.extend_from_slice(tag);
+            out.extend_from_slice(&v.to_le_bytes());
+        }
+        5 => {
+            let v = value
+                .parse::<u32>()
+                .ok()
+                .or_else(|| value.parse::<i32>().ok().map(|v| v as u32))
+                .or_else(|| value.parse::<f32>().ok().map(f32::to_bits))
+                .ok_or_else(|| invalid("32 bit value"))?;
+            out.extend_from_slice(tag);
+            out.extend_from_slice(&v.to_le_bytes());
+        }
+        2 => write_length_delimited(tag, value.as_bytes(), out),
+        _ => {
+            return Err(format!(
+                "field {} is a group, only the fields inside it can be set",
+                span.number
+            ))
+        }
+    }
+    Ok(())
+}
+
+/// Parses an unsigned or signed integer. Negative numbers are stored as two's complement.
+fn parse_integer(value: &str) -> Option<u64> {
+    value
+        .parse::<u64>()
+        .ok()
+        .or_else(|| value.parse::<i64>().ok().map(|v| v as u64))
+}
+
+fn write_length_delimited(tag: &[u8], payload: &[u8], out: &mut Vec<u8>) {
+    out.extend_from_slice(tag);
+    write_varint(payload.len() as u128, out);
+    out.extend_from_slice(payload);
+}
+
+fn print_path(path: &[u64]) -> String {
+    path.iter().map(|number| format!(".{}", number)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 1: 150, 2: "ab", 3 { 1: 1, 2: "xy" }, 4: fixed32 1
+    const MESSAGE: &[u8] = b"\x08\x96\x01\x12\x02ab\x1a\x06\x08\x01\x12\x02xy\x25\x01\x00\x00\x00";
+
+    #[test]
+    fn edit_fields_sets_values() {
+        assert_eq!(
+            edit_fields(MESSAGE, &[1], Edit::Set("1")).unwrap(),
+            b"\x08\x01\x12\x02ab\x1a\x06\x08\x01\x12\x02xy\x25\x01\x00\x00\x00"
+        );
+        assert_eq!(
+            edit_fields(MESSAGE, &[2], Edit::Set("abc")).unwrap(),
+            b"\x08\x96\x01\x12\x03abc\x1a\x06\x08\x01\x12\x02xy\x25\x01\x00\x00\x00"
+        );
+        assert_eq!(
+            edit_fields(MESSAGE, &[4], Edit::Set("1.5")).unwrap(),
+            b"\x08\x96\x01\x12\x02ab\x1a\x06\x08\x01\x12\x02xy\x25\x00\x00\xc0\x3f"
+        );
+        assert_eq!(
+            edit_fields(b"\x08\x01", &[1], Edit::Set("-1")).unwrap(),
+            b"\x08\xff\xff\xff\xff\xff\xff\xff\xff\xff\x01"
+        );
+    }
+
+    #[test]
+    fn edit_fields_recomputes_lengths() {
+        assert_eq!(
+            edit_fields(MESSAGE, &[3, 2], Edit::Set("New Name")).unwrap(),
+            b"\x08\x96\x01\x12\x02ab\x1a\x0c\x08\x01\x12\x08New Name\x25\x01\x00\x00\x00"
+        );
+        assert_eq!(
+            edit_fields(MESSAGE, &[3, 2], Edit::Delete).unwrap(),
+            b"\x08\x96\x01\x12\x02ab\x1a\x02\x08\x01\x25\x01\x00\x00\x00"
+        );
+    }
+
+    #[test]
+    fn edit_fields_deletes_fields() {
+        assert_eq!(
+            edit_fields(MESSAGE, &[3], Edit::Delete).unwrap(),
+            b"\x08\x96\x01\x12\x02ab\x25\x01\x00\x00\x00"
+        );
+        // All repeated fields are removed
+        assert_eq!(
+            edit_fields(b"\x08\x01\x10\x02\x08\x03", &[1], Edit::Delete).unwrap(),
+            b"\x10\x02"
+        );
+    }
+
+    #[test]
+    fn edit_fields_creates_missing_fields() {
+        assert_eq!(
+            edit_fields(b"\x08\x01", &[2], Edit::Set("5")).unwrap(),
+            b"\x08\x01\x10\x05"
+        );
+        assert_eq!(
+            edit_fields(b"\x08\x01", &[2, 3], Edit::Set("hi")).unwrap(),
+            b"\x08\x01\x12\x04\x1a\x02hi"
+        );
+    }
+
+    #[test]
+    fn edit_fields_creates_fields_only_if_missing_everywhere() {
+        // 3 { 1: 1 }, 3 { 3 { 1: 1 } }
+        let repeated = b"\x1a\x02\x08\x01\x1a\x04\x1a\x02\x08\x01";
+        assert_eq!(
+            edit_fields(repeated, &[3, 3, 2], Edit::Set("X")).unwrap(),
+            b"\x1a\x02\x08\x01\x1a\x07\x1a\x05\x08\x01\x12\x01X"
+        );
+        assert_eq!(
+            edit_fields(repeated, &[3, 2], Edit::Set("X")).unwrap_err(),
+            ".3 exists 2 times, so it is unclear where to create .2"
+        );
+        // Negative numbers are varints like when setting existing fields
+        assert_eq!(
+            edit_fields(b"", &[1], Edit::Set("-5")).unwrap(),
+            b"\x08\xfb\xff\xff\xff\xff\xff\xff\xff\xff\x01"
+        );
+    }
+
+    #[test]
+    fn edit_fields_keeps_encoding() {
+        // Over-long tag and field order are preserved
+        assert_eq!(
+            edit_fields(b"\x90\x00\x01\x08\x02", &[1], Edit::Set("3")).unwrap(),
+            b"\x90\x00\x01\x08\x03"
+        );
+    }
+
+    #[test]
+    fn edit_fields_keeps_groups() {
+        // 1 (group) { 1: 1 }, 2: 2
+        let group = b"\x0b\x08\x01\x0c\x10\x02";
+        assert_eq!(
+            edit_fields(group, &[1, 1], Edit::Set("5")).unwrap(),
+            b"\x0b\x08\x05\x0c\x10\x02"
+        );
+        assert_eq!(
+            edit_fields(group, &[1, 3], Edit::Set("x")).unwrap(),
+            b"\x0b\x08\x01\x1a\x01x\x0c\x10\x02"
+        );
+        assert_eq!(
+            edit_fields(group, &[1, 1], Edit::Delete).unwrap(),
+            b"\x0b\x0c\x10\x02"
+        );
+        assert_eq!(edit_fields(group, &[1], Edit::Delete).unwrap(), b"\x10\x02");
+        assert_eq!(
+            edit_fields(group, &[1], Edit::Set("hello")).unwrap_err(),
+            "field 1 is a group, only the fields inside it can be set"
+        );
+    }
+
+    #[test]
+    fn edit_fields_reports_errors() {
+        assert_eq!(
+            edit_fields(MESSAGE, &[5], Edit::Delete).unwrap_err(),
+            "no field found at .5"
+        );
+        assert_eq!(
+            edit_fields(MESSAGE, &[1], Edit::Set("abc")).unwrap_err(),
+            "'abc' is not a valid varint for field 1"
+        );
+        assert_eq!(
+            edit_fields(MESSAGE, &[1, 2], Edit::Delete).unwrap_err(),
+            "field 1 is not a message"
+        );
+        assert_eq!(
+            edit_fields(MESSAGE, &[2, 1], Edit::Delete).unwrap_err(),
+            "field 2: not a valid protobuf serialization"
+        );
+        assert_eq!(
+            edit_fields(MESSAGE, &[], Edit::Delete).unwrap_err(),
+            "the path must not be empty"
+        );
+    }
+}
@@ -0,0 +1,7 @@
+mod edit;
+mod text;
+mod wire;
+
+pub use edit::{edit_fields, Edit};
+pub use text::parse_text;
+pub use wire::encode_fields;
@@ -0,0 +1,346 @@
+//! Parsing of the text output of the decoder back into fields.
+
+use decode_raw::strip_colors;
+
+/// A field read from the text output
+#[derive(Debug, PartialEq, Clone)]
+pub struct TextField {
+    pub number: u64,
+    pub value: TextValue,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum TextValue {
+    Varint(u128),
+    Fixed64([u8; 8]),
+    Fixed32([u8; 4]),
+    Bytes(Vec<u8>),
+    /// A nested message, optionally stored as encoded string
+    Message(Vec<TextField>, Option<StringEncoding>),
+    /// A nested message between start and end group tags
+    Group(Vec<TextField>),
+    /// The fields of a group that were not decoded
+    GroupBytes(Vec<u8>),
+}
+
+/// The encoding of a string that contains a nested message
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum StringEncoding {
+    Base64,
+    Hex,
+    /// Hex with uppercase letters
+    UpperHex,
+}
+
+/// Parses the text printed by the decoder.
+///
+/// Annotations like offsets, wire types or detected timestamps are ignored, as well
+/// as lines starting with `#` like the alternatives of `--show-alternatives`.
+/// Values that were truncated or pretty-printed as JSON cannot be restored
+/// and result in an error.
+pub fn parse_text(input: &str) -> Result<Vec<TextField>, String> {
+    // The enclosing messages of the current one, innermost last
+    let mut stack: Vec<(u64, Option<NestedEncoding>, Vec<TextField>)> = Vec::new();
+    let mut fields = Vec::new();
+    for (index, line) in input.lines().enumerate() {
+        let line = strip_colors(line);
+        let line = line.trim_start_matches(['·', ' ']);
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let error = |message: &str| format!("line {}: {}", index + 1, message);
+        if line.starts_with('}') {
+            let (number, encoding, parent) = stack.pop().ok_or_else(|| error("unexpected '}'"))?;
+            let nested = std::mem::replace(&mut fields, parent);
+            let value = match encoding {
+                Some(NestedEncoding::Group) => TextValue::Group(nested),
+                Some(NestedEncoding::String(encoding)) => {
+                    TextValue::Message(nested, Some(encoding))
+                }
+                None => TextValue::Message(nested, None),
+            };
+            fields.push(TextField { number, value });
+            continue;
+        }
+
+        let path_end = line
+            .find(|c: char| !c.is_ascii_digit() && c != '.')
+            .unwrap_or(line.len());
+        let number = line[..path_end]
+            .rsplit('.')
+            .next()
+            .and_then(|n| n.parse::<u64>().ok())
+            .ok_or_else(|| error("expected a field number"))?;
+        let rest = &line[path_end..];
+
+        if let Some(annotation) = rest.strip_prefix(" {") {
+            let encoding = if annotation.starts_with(" (group)") {
+                Some(NestedEncoding::Group)
+            } else if annotation.starts_with(" (base64)") {
+                Some(NestedEncoding::String(StringEncoding::Base64))
+            } else if annotation.starts_with(" (hex)") {
+                Some(NestedEncoding::String(StringEncoding::Hex))
+            } else if annotation.starts_with(" (HEX)") {
+                Some(NestedEncoding::String(StringEncoding::UpperHex))
+            } else {
+                None
+            };
+            stack.push((number, encoding, std::mem::take(&mut fields)));
+        } else if let Some(value) = rest.strip_prefix(": ") {
+            let value = parse_value(value).map_err(|message| error(&message))?;
+            fields.push(TextField { number, value });
+        } else {
+            return Err(error("expected ': ' or ' {' after the field number"));
+        }
+    }
+    if !stack.is_empty() {
+        return Err("unexpected end of input, expected '}'".to_string());
+    }
+    Ok(fields)
+}
+
+/// How a nested message other than a length-delimited field is encoded
+enum NestedEncoding {
+    Group,
+    String(StringEncoding),
+}
+
+fn parse_value(value: &str) -> Result<TextValue, String> {
+    if let Some(rest) = value.strip_prefix("(64 bit) ") {
+        let v: u64 = parse_number(rest)?;
+        return Ok(TextValue::Fixed64(v.to_le_bytes()));
+    }
+    if let Some(rest) = value.strip_prefix("(32 bit) ") {
+        let v: u32 = parse_number(rest)?;
+        return Ok(TextValue::Fixed32(v.to_le_bytes()));
+    }
+    if let Some(rest) = value.strip_prefix('(') {
+        let (length, rest) = rest
+            .split_once(" bytes")
+            .ok_or_else(|| "expected '(N bytes)'".to_string())?;
+        let length: usize = length.parse().map_err(|_| "invalid length".to_string())?;
+        let (annotation, rest) = rest
+            .split_once(") ")
+            .ok_or_else(|| "expected '(N bytes)'".to_string())?;
+        let bytes = parse_bytes(rest)?;
+        if bytes.len() != length {
+            return Err(format!(
+                "expected {} bytes but the value has {}",
+                length,
+                bytes.len()
+            ));
+        }
+        if annotation == ", group" {
+            return Ok(TextValue::GroupBytes(bytes));
+        }
+        return Ok(TextValue::Bytes(bytes));
+    }
+    Ok(TextValue::Varint(parse_number(value)?))
+}
+
+fn parse_number<T: std::str::FromStr>(value: &str) -> Result<T, String> {
+    let end = value
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(value.len());
+    value[..end]
+        .parse()
+        .map_err(|_| format!("invalid number '{}'", &value[..end]))
+}
+
+/// Parses a quoted string or hex encoded binary data
+fn parse_bytes(value: &str) -> Result<Vec<u8>, String> {
+    let (bytes, rest) = match value.chars().next() {
+        Some(quote @ ('\'' | '"')) => {
+            let end =
+                closing_quote(value, quote).ok_or_else(|| "missing closing quote".to_string())?;
+            let unescaped = snailquote::unescape(&value[..end]).map_err(|err| err.to_string())?;
+            (unescaped.into_bytes(), &value[end..])
+        }
+        Some('{' | '[') => return Err("pretty-printed JSON cannot be encoded".to_string()),
+        _ => {
+            let end = value
+                .find(|c: char| !c.is_ascii_hexdigit())
+                .unwrap_or(value.len());
+            let bytes = hex::decode(&value[..end]).map_err(|err| err.to_string())?;
+            (bytes, &value[end..])
+        }
+    };
+    if rest.starts_with('…') {
+        return Err("the value is truncated, decode with --full".to_string());
+    }
+    Ok(bytes)
+}
+
+/// The byte offset after the quote that closes the string starting at the beginning of value.
+/// Single quoted strings contain no escapes.
+fn closing_quote(value: &str, quote: char) -> Option<usize> {
+    let mut escaped = false;
+    for (i, c) in value.char_indices().skip(1) {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if quote == '"' => escaped = true,
+            c if c == quote => return Some(i + 1),
+            _ => {}
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn field(number: u64, value: TextValue) -> TextField {
+        TextField { number, value }
+    }
+
+    #[test]
+    fn parse_text_works() {
+        let input = "1: \x1b[31m150\x1b[0m\n\
+                     2: (8 bytes) \x1b[32m'John Doe'\x1b[0m\n\
+                     3 {\n\
+                     · 4: (32 bit) 1107820544 / 34\n\
+                     · 5 {\n\
+                     · · 6: (64 bit) 18446744073709551596 / -20 / NaN\n\
+                     · }\n\
+                     · 7: (3 bytes) 00ff10\n\
+                     }\n\
+                     8: 1\n";
+        assert_eq!(
+            parse_text(input).unwrap(),
+            vec![
+                field(1, TextValue::Varint(150)),
+                field(2, TextValue::Bytes(b"John Doe".to_vec())),
+                field(
+                    3,
+                    TextValue::Message(
+                        vec![
+                            field(4, TextValue::Fixed32(1107820544u32.to_le_bytes())),
+                            field(
+                                5,
+                                TextValue::Message(
+                                    vec![field(6, TextValue::Fixed64((-20i64).to_le_bytes()))],
+                                    None
+                                )
+                            ),
+                            field(7, TextValue::Bytes(vec![0x00, 0xff, 0x10])),
+                        ],
+                        None
+                    )
+                ),
+                field(8, TextValue::Varint(1)),
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_text_ignores_annotations() {
+        let input = ".1: 150  [wire type 0: varint]  @0 (tag 1, payload 1..3)\n\
+                     .2 { (timestamp) 2021-01-01T00:00:00Z\n\
+                     .2.1: 1609459200\n\
+                     }\n\
+                     .3 { (base64)\n\
+                     .3.1: (6 bytes) 'Nobody'\n\
+                     }\n\
+                     .3 { (HEX)\n\
+                     }\n\
+                     .4: (32 bytes) 0000000000000000000000000000000000000000000000000000000000000000 (hash)\n";
+        let fields = parse_text(input).unwrap();
+        assert_eq!(fields.len(), 5);
+        assert_eq!(fields[0], field(1, TextValue::Varint(150)));
+        assert_eq!(
+            fields[1],
+            field(
+                2,
+                TextValue::Message(vec![field(1, TextValue::Varint(1609459200))], None)
+            )
+        );
+        assert_eq!(
+            fields[2],
+            field(
+                3,
+                TextValue::Message(
+                    vec![field(1, TextValue::Bytes(b"Nobody".to_vec()))],
+                    Some(StringEncoding::Base64)
+                )
+            )
+        );
+        assert_eq!(
+            fields[3],
+            field(
+                3,
+                TextValue::Message(vec![], Some(StringEncoding::UpperHex))
+            )
+        );
+        assert_eq!(fields[4], field(4, TextValue::Bytes(vec![0; 32])));
+    }
+
+    #[test]
+    fn parse_text_reads_groups() {
+        let input = "1 { (group)\n\
+                     · 2: (2 bytes, group) 1801  [not decoded, max depth reached]\n\
+                     }\n";
+        assert_eq!(
+            parse_text(input).unwrap(),
+            vec![field(
+                1,
+                TextValue::Group(vec![field(2, TextValue::GroupBytes(vec![0x18, 0x01]))])
+            )]
+        );
+    }
+
+    #[test]
+    fn parse_text_skips_comments() {
+        let input = "1: (4 bytes) 10010801\n\
+                     # 1 {  [alternative: message with confidence 0.80]\n\
+                     # · 2: 1\n\
+                     # }\n";
+        assert_eq!(
+            parse_text(input).unwrap(),
+            vec![field(1, TextValue::Bytes(vec![0x10, 0x01, 0x08, 0x01]))]
+        );
+    }
+
+    #[test]
+    fn parse_text_unescapes_strings() {
+        let parse = |line: &str| match &parse_text(line).unwrap()[0].value {
+            TextValue::Bytes(bytes) => String::from_utf8(bytes.clone()).unwrap(),
+            other => panic!("unexpected {:?}", other),
+        };
+        assert_eq!(parse(r#"1: (3 bytes) "foo""#), "foo");
+        assert_eq!(parse(r#"1: (7 bytes) 'foo bar'"#), "foo bar");
+        assert_eq!(parse(r#"1: (4 bytes) 'fo"o'"#), "fo\"o");
+        assert_eq!(parse(r#"1: (5 bytes) "f'o\"o""#), "f'o\"o");
+        assert_eq!(parse(r#"1: (2 bytes) "a\n""#), "a\n");
+        assert_eq!(parse(r#"1: (0 bytes) """#), "");
+    }
+
+    #[test]
+    fn parse_text_rejects_lossy_output() {
+        assert_eq!(
+            parse_text("1: (300 bytes) 0011…").unwrap_err(),
+            "line 1: the value is truncated, decode with --full"
+        );
+        assert_eq!(
+            parse_text("1: (300 bytes) 'abc'…'xyz'").unwrap_err(),
+            "line 1: the value is truncated, decode with --full"
+        );
+        assert_eq!(
+            parse_text("1: (13 bytes) {\n  \"my\": \"json\"\n}").unwrap_err(),
+            "line 1: pretty-printed JSON cannot be encoded"
+        );
+        assert_eq!(
+            parse_text("1: (4 bytes) 'abc'").unwrap_err(),
+            "line 1: expected 4 bytes but the value has 3"
+        );
+        assert_eq!(
+            parse_text("1 {\n· 2: 1").unwrap_err(),
+            "unexpected end of input, expected '}'"
+        );
+        assert_eq!(parse_text("}").unwrap_err(), "line 1: unexpected '}'");
+        assert_eq!(
+            parse_text("foo: 1").unwrap_err(),
+            "line 1: expected a field number"
+        );
+    }
+}
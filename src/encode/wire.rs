@@ -0,0 +1,175 @@
+//! Serialization of fields in the protobuf wire format.
+
+use base64::Engine;
+
+use super::text::{StringEncoding, TextField, TextValue};
+
+/// Writes the varint encoding of the value with the minimal number of bytes
+pub fn write_varint(mut value: u128, out: &mut Vec<u8>) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            out.push(byte);
+            return;
+        }
+        out.push(byte | 0x80);
+    }
+}
+
+fn write_tag(number: u64, wire_type: u8, out: &mut Vec<u8>) {
+    write_varint(((number as u128) << 3) | wire_type as u128, out);
+}
+
+fn write_length_delimited(number: u64, payload: &[u8], out: &mut Vec<u8>) {
+    write_tag(number, 2, out);
+    write_varint(payload.len() as u128, out);
+    out.extend_from_slice(payload);
+}
+
+/// Serializes the fields as protobuf message
+pub fn encode_fields(fields: &[TextField]) -> Vec<u8> {
+    let mut out = Vec::new();
+    for field in fields {
+        match &field.value {
+            TextValue::Varint(v) => {
+                write_tag(field.number, 0, &mut out);
+                write_varint(*v, &mut out);
+            }
+            TextValue::Fixed64(v) => {
+                write_tag(field.number, 1, &mut out);
+                out.extend_from_slice(v);
+            }
+            TextValue::Fixed32(v) => {
+                write_tag(field.number, 5, &mut out);
+                out.extend_from_slice(v);
+            }
+            TextValue::Bytes(v) => write_length_delimited(field.number, v, &mut out),
+            TextValue::Message(fields, encoding) => {
+                let nested = encode_fields(fields);
+                let payload = match encoding {
+                    None => nested,
+                    Some(StringEncoding::Base64) => base64::engine::general_purpose::STANDARD
+                        .encode(nested)
+                        .into_bytes(),
+                    Some(StringEncoding::Hex) => hex::encode(nested).into_bytes(),
+                    Some(StringEncoding::UpperHex) => hex::encode_upper(nested).into_bytes(),
+                };
+                write_length_delimited(field.number, &payload, &mut out);
+            }
+            TextValue::Group(fields) => {
+                write_tag(field.number, 3, &mut out);
+                out.extend_from_slice(&encode_fields(fields));
+                write_tag(field.number, 4, &mut out);
+            }
+            TextValue::GroupBytes(v) => {
+                write_tag(field.number, 3, &mut out);
+                out.extend_from_slice(v);
+                write_tag(field.number, 4, &mut out);
+            }
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::encode::parse_text;
+    use decode_raw::{Formatter, Message, ParseConfig, TextFormatter};
+
+    /// Decodes the bytes to text and encodes the text again
+    fn round_trip(bytes: &[u8], config: ParseConfig) -> Vec<u8> {
+        let message = Message::decode(bytes, config).unwrap();
+        let text = TextFormatter::default().format(&message);
+        encode_fields(&parse_text(&text).unwrap())
+    }
+
+    #[test]
+    fn write_varint_works() {
+        let varint = |value| {
+            let mut out = Vec::new();
+            write_varint(value, &mut out);
+            out
+        };
+        assert_eq!(varint(0), b"\x00");
+        assert_eq!(varint(1), b"\x01");
+        assert_eq!(varint(127), b"\x7f");
+        assert_eq!(varint(150), b"\x96\x01");
+        assert_eq!(varint(95941545151), b"\xbf\x99\xbf\xb4\xe5\x02");
+        assert_eq!(
+            varint(u64::MAX as u128),
+            b"\xff\xff\xff\xff\xff\xff\xff\xff\xff\x01"
+        );
+    }
+
+    #[test]
+    fn encode_fields_works() {
+        let fields = vec![
+            TextField {
+                number: 1,
+                value: TextValue::Varint(150),
+            },
+            TextField {
+                number: 2,
+                value: TextValue::Bytes(b"ab".to_vec()),
+            },
+            TextField {
+                number: 3,
+                value: TextValue::Message(
+                    vec![TextField {
+                        number: 4,
+                        value: TextValue::Fixed32([1, 2, 3, 4]),
+                    }],
+                    None,
+                ),
+            },
+            TextField {
+                number: 5,
+                value: TextValue::Fixed64([1, 2, 3, 4, 5, 6, 7, 8]),
+            },
+        ];
+        assert_eq!(
+            encode_fields(&fields),
+            b"\x08\x96\x01\x12\x02ab\x1a\x05\x25\x01\x02\x03\x04\x29\x01\x02\x03\x04\x05\x06\x07\x08"
+        );
+    }
+
+    #[test]
+    fn encode_fields_round_trips_groups() {
+        // 1 (group) { 2 (group) { 3: 1 } }, 4 { 5: 1 }
+        let bytes = b"\x0b\x13\x18\x01\x14\x0c\x22\x02\x28\x01";
+        assert_eq!(round_trip(bytes, ParseConfig::default()), bytes);
+        let config = ParseConfig {
+            max_depth: 1,
+            ..Default::default()
+        };
+        assert_eq!(round_trip(bytes, config), bytes);
+    }
+
+    #[test]
+    fn encode_fields_encodes_strings() {
+        let nested = vec![TextField {
+            number: 1,
+            value: TextValue::Bytes(b"Nobody".to_vec()),
+        }];
+        let fields = vec![
+            TextField {
+                number: 2,
+                value: TextValue::Message(nested.clone(), Some(StringEncoding::Base64)),
+            },
+            TextField {
+                number: 3,
+                value: TextValue::Message(nested.clone(), Some(StringEncoding::Hex)),
+            },
+            TextField {
+                number: 4,
+                value: TextValue::Message(nested, Some(StringEncoding::UpperHex)),
+            },
+        ];
+        assert_eq!(
+            encode_fields(&fields),
+            &b"\x12\x0cCgZOb2JvZHk=\x1a\x100a064e6f626f6479\x22\x100A064E6F626F6479"[..]
+        );
+    }
+}
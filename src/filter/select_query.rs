@@ -1,37 +1,88 @@
 use std::ops::Deref;
 
 use crate::parse::Entry;
+use crate::tree::Field;
 
+/// A path of field numbers like `.3.2`. Each step may pick one of the fields with
+/// the same number by its position, e.g. `.3[1].2`.
 #[derive(Default, Clone, PartialEq, Debug)]
-pub struct SelectQuery(Vec<u64>);
+pub struct SelectQuery {
+    path: Vec<u64>,
+    indices: Vec<Option<usize>>,
+}
 
 impl Deref for SelectQuery {
     type Target = [u64];
 
     fn deref(&self) -> &[u64] {
-        &self.0
+        &self.path
     }
 }
 
 impl SelectQuery {
     pub fn parse(input: &str) -> Result<Self, String> {
         // Trim leading .
-        let prepared = if input.starts_with('.') {
-            &input[1..]
-        } else {
-            input
-        };
+        let prepared = input.strip_prefix('.').unwrap_or(input);
         if prepared.is_empty() {
             return Ok(SelectQuery::default());
         }
-        let components: Vec<&str> = prepared.split('.').collect();
-        let path: Vec<u64> = components
+        let (path, indices) = prepared
+            .split('.')
+            .map(parse_component)
+            .collect::<Result<Vec<_>, _>>()?
             .into_iter()
-            .map(|str| str.parse::<u64>())
-            .collect::<Result<Vec<_>, _>>()
-            .map_err(|err| err.to_string())?;
-        Ok(SelectQuery(path))
+            .unzip();
+        Ok(SelectQuery { path, indices })
+    }
+
+    /// The position among the fields with the same number required at the given depth
+    pub fn index_at(&self, depth: usize) -> Option<usize> {
+        self.indices.get(depth).copied().flatten()
+    }
+
+    /// Checks if the field at the given position among the fields with the same number
+    /// may be selected at the given depth
+    pub fn matches_index(&self, depth: usize, index: usize) -> bool {
+        self.index_at(depth)
+            .is_none_or(|required| required == index)
     }
+
+    pub fn has_indices(&self) -> bool {
+        self.indices.iter().any(Option::is_some)
+    }
+
+    /// The fields of a message at the given depth that have the number of the path
+    /// and the required position among them, if any
+    pub(crate) fn select_fields<'m, 'a>(
+        &self,
+        fields: &'m [Field<'a>],
+        depth: usize,
+    ) -> Vec<&'m Field<'a>> {
+        let number = self.path[depth];
+        let mut matching = fields.iter().filter(|field| field.number == number);
+        match self.index_at(depth) {
+            Some(index) => matching.nth(index).into_iter().collect(),
+            None => matching.collect(),
+        }
+    }
+}
+
+/// Parses a step of the path like `3` or `3[1]`
+fn parse_component(input: &str) -> Result<(u64, Option<usize>), String> {
+    let (number, index) = match input.split_once('[') {
+        Some((number, rest)) => {
+            let index = rest
+                .strip_suffix(']')
+                .ok_or_else(|| format!("expected ] after index in '{}'", input))?;
+            (
+                number,
+                Some(index.parse::<usize>().map_err(|err| err.to_string())?),
+            )
+        }
+        None => (input, None),
+    };
+    let number = number.parse::<u64>().map_err(|err| err.to_string())?;
+    Ok((number, index))
 }
 
 /// Check if the given entry is selected by the select query.
@@ -45,25 +96,64 @@ pub fn is_selected(entry: &Entry, query: &SelectQuery) -> bool {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::EntryValue;
+    use crate::{EntryValue, FieldSpan, Message, ParseConfig};
 
     #[test]
     fn parse_can_parse_empty() {
-        assert_eq!(SelectQuery::parse("").unwrap().0, &[]);
-        assert_eq!(SelectQuery::parse(".").unwrap().0, &[]);
+        assert_eq!(SelectQuery::parse("").unwrap().path, &[] as &[u64]);
+        assert_eq!(SelectQuery::parse(".").unwrap().path, &[] as &[u64]);
     }
 
     #[test]
     fn parse_can_parse_simple() {
-        assert_eq!(SelectQuery::parse("1").unwrap().0, &[1]);
-        assert_eq!(SelectQuery::parse(".1").unwrap().0, &[1]);
+        assert_eq!(SelectQuery::parse("1").unwrap().path, &[1]);
+        assert_eq!(SelectQuery::parse(".1").unwrap().path, &[1]);
     }
 
     #[test]
     fn parse_can_parse_multi() {
-        assert_eq!(SelectQuery::parse("1.2").unwrap().0, &[1, 2]);
-        assert_eq!(SelectQuery::parse(".1.2").unwrap().0, &[1, 2]);
-        assert_eq!(SelectQuery::parse("3.3").unwrap().0, &[3, 3]);
+        assert_eq!(SelectQuery::parse("1.2").unwrap().path, &[1, 2]);
+        assert_eq!(SelectQuery::parse(".1.2").unwrap().path, &[1, 2]);
+        assert_eq!(SelectQuery::parse("3.3").unwrap().path, &[3, 3]);
+    }
+
+    #[test]
+    fn parse_can_parse_indices() {
+        let query = SelectQuery::parse(".3[1].2").unwrap();
+        assert_eq!(*query, [3, 2]);
+        assert_eq!(query.index_at(0), Some(1));
+        assert_eq!(query.index_at(1), None);
+        assert_eq!(query.index_at(2), None);
+        assert!(query.matches_index(0, 1));
+        assert!(!query.matches_index(0, 0));
+        assert!(query.matches_index(1, 0));
+        assert!(query.has_indices());
+        assert!(!SelectQuery::parse(".3.2").unwrap().has_indices());
+
+        let err = SelectQuery::parse(".3[1").unwrap_err();
+        assert_eq!(err, "expected ] after index in '3[1'");
+        let err = SelectQuery::parse(".3[x]").unwrap_err();
+        assert_eq!(err, "invalid digit found in string");
+        let err = SelectQuery::parse(".[1]").unwrap_err();
+        assert_eq!(err, "cannot parse integer from empty string");
+    }
+
+    #[test]
+    fn select_fields_works() {
+        // 1: 1, 2: 2, 1: 3
+        let message = Message::decode(b"\x08\x01\x10\x02\x08\x03", ParseConfig::default()).unwrap();
+        let offsets = |query: &str| -> Vec<usize> {
+            SelectQuery::parse(query)
+                .unwrap()
+                .select_fields(&message.fields, 0)
+                .iter()
+                .map(|field| field.span.start)
+                .collect()
+        };
+        assert_eq!(offsets(".1"), vec![0, 4]);
+        assert_eq!(offsets(".1[1]"), vec![4]);
+        assert!(offsets(".1[2]").is_empty());
+        assert!(offsets(".3").is_empty());
     }
 
     #[test]
@@ -94,6 +184,9 @@ mod tests {
         let entry = Entry {
             path: vec![1, 2, 3],
             value: EntryValue::Varint(1),
+            span: FieldSpan::default(),
+            limit: None,
+            confidence: None,
         };
         assert!(is_selected(&entry, &SelectQuery::parse(".1").unwrap()));
         assert!(is_selected(&entry, &SelectQuery::parse(".1.2").unwrap()));
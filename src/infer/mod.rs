@@ -0,0 +1,5 @@
+mod proto_file;
+mod schema;
+
+pub use proto_file::format_proto;
+pub use schema::{infer_schema, FieldType, InferredField, InferredMessage};
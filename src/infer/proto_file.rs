@@ -0,0 +1,130 @@
+//! Writing an inferred schema as `.proto` file.
+
+use std::fmt::Write;
+
+use super::schema::{FieldType, InferredField, InferredMessage};
+use crate::parse::wire_type_name;
+
+/// Writes the schema as proto3 file with a message of the given name.
+/// Fields are named `field_N` and the types of nested messages `FieldN`.
+pub fn format_proto(schema: &InferredMessage, name: &str) -> String {
+    let mut out = String::from("syntax = \"proto3\";\n\n");
+    write_message(schema, name, 0, &mut out);
+    out
+}
+
+fn write_message(message: &InferredMessage, name: &str, depth: usize, out: &mut String) {
+    let indent = "  ".repeat(depth);
+    writeln!(out, "{}message {} {{", indent, name).unwrap();
+    for (number, field) in &message.fields {
+        let label = if field.is_repeated() { "repeated " } else { "" };
+        write!(
+            out,
+            "{}  {}{} field_{} = {};",
+            indent,
+            label,
+            type_name(*number, field.field_type()),
+            number,
+            number
+        )
+        .unwrap();
+        let notes = notes(field);
+        if !notes.is_empty() {
+            write!(out, " // {}", notes.join(", ")).unwrap();
+        }
+        out.push('\n');
+    }
+    for (number, field) in &message.fields {
+        if matches!(field.field_type(), FieldType::Message | FieldType::Group) {
+            out.push('\n');
+            write_message(
+                &field.message,
+                &type_name(*number, field.field_type()),
+                depth + 1,
+                out,
+            );
+        }
+    }
+    writeln!(out, "{}}}", indent).unwrap();
+}
+
+fn type_name(number: u64, field_type: FieldType) -> String {
+    match field_type {
+        FieldType::Uint64 | FieldType::PackedUint64 => "uint64",
+        FieldType::Int64 => "int64",
+        FieldType::Fixed64 => "fixed64",
+        FieldType::Fixed32 => "fixed32",
+        FieldType::String => "string",
+        FieldType::Bytes => "bytes",
+        FieldType::Message | FieldType::Group => return format!("Field{}", number),
+    }
+    .to_string()
+}
+
+/// Remarks about the field that the declaration cannot express
+fn notes(field: &InferredField) -> Vec<String> {
+    let mut out = Vec::new();
+    if field.field_type() == FieldType::Group {
+        out.push("encoded as group".to_string());
+    }
+    for (wire_type, count) in field.conflicts() {
+        out.push(format!(
+            "also seen as {} {} {}",
+            wire_type_name(wire_type),
+            count,
+            if count == 1 { "time" } else { "times" }
+        ));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::infer::infer_schema;
+    use crate::parse::ParseConfig;
+    use crate::tree::Message;
+
+    #[test]
+    fn format_proto_works() {
+        let samples = [
+            // 1: 150, 2 { 1: "ab", 2 { 1: 1 } }, 3: [1, 2], 4: 1
+            &b"\x08\x96\x01\x12\x08\x0a\x02ab\x12\x02\x08\x01\x1a\x02\x01\x02\x20\x01"[..],
+            // 1: 1, 1: 2, 3: 3, 4: fixed32
+            b"\x08\x01\x08\x02\x18\x03\x25\x01\x02\x03\x04",
+        ];
+        let samples: Vec<Message> = samples
+            .iter()
+            .map(|bytes| Message::parse(bytes, ParseConfig::default()).unwrap())
+            .collect();
+        assert_eq!(
+            format_proto(&infer_schema(&samples), "Sample"),
+            "syntax = \"proto3\";\n\
+             \n\
+             message Sample {\n\
+            \x20 repeated uint64 field_1 = 1;\n\
+            \x20 Field2 field_2 = 2;\n\
+            \x20 repeated uint64 field_3 = 3;\n\
+            \x20 uint64 field_4 = 4; // also seen as 32 bit 1 time\n\
+             \n\
+            \x20 message Field2 {\n\
+            \x20   string field_1 = 1;\n\
+            \x20   Field2 field_2 = 2;\n\
+             \n\
+            \x20   message Field2 {\n\
+            \x20     uint64 field_1 = 1;\n\
+            \x20   }\n\
+            \x20 }\n\
+             }\n"
+        );
+    }
+
+    #[test]
+    fn format_proto_marks_groups() {
+        // 1 (group) { 2: 1 }
+        let message = Message::parse(b"\x0b\x10\x01\x0c", ParseConfig::default()).unwrap();
+        let proto = format_proto(&infer_schema(&[message]), "Sample");
+        assert!(proto.contains("  Field1 field_1 = 1; // encoded as group\n"));
+        assert!(proto.contains("  message Field1 {\n    uint64 field_2 = 2;\n  }\n"));
+    }
+}
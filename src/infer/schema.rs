@@ -0,0 +1,280 @@
+//! Guessing the schema of a message type from decoded samples.
+//!
+//! The fields of all samples are merged by number. Nested messages are merged
+//! recursively, so every sample contributes to the fields it contains. Which type
+//! a field has is decided from all of its values at the end.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::display::{show_as, ShowAs, ShowAsConfig};
+use crate::parse::read_varint;
+use crate::tree::{Field, FieldValue, Message};
+
+/// Binary values needed to guess packed varints when no unpacked value was seen.
+/// Random bytes are a valid varint sequence whenever their last byte is below 0x80.
+const MIN_PACKED_VALUES: usize = 3;
+
+/// The merged fields of all samples of a message
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct InferredMessage {
+    pub fields: BTreeMap<u64, InferredField>,
+}
+
+/// What was seen of a field across all samples
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct InferredField {
+    /// How often each wire type was seen
+    pub wire_types: BTreeMap<u8, usize>,
+    /// Seen more than once in the same message
+    pub repeated: bool,
+    /// Seen varints above the int64 range, i.e. negative int64 or int32 values
+    pub negative: bool,
+    /// Number of empty length-delimited values
+    pub empty: usize,
+    /// Number of values decoded as message or group
+    pub messages: usize,
+    /// Number of non-empty length-delimited values that are printable text
+    pub strings: usize,
+    /// Number of non-empty length-delimited values that are neither message nor text
+    pub binary: usize,
+    /// Number of the binary values that are a valid sequence of varints
+    pub packed: usize,
+    /// The merged fields of all values decoded as message or group
+    pub message: InferredMessage,
+}
+
+/// The best guess for the type of a field
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum FieldType {
+    Uint64,
+    Int64,
+    Fixed64,
+    Fixed32,
+    String,
+    Bytes,
+    Message,
+    /// A message encoded with start and end group tags
+    Group,
+    /// Repeated varints, packed into length-delimited values
+    PackedUint64,
+}
+
+/// Merges the fields of all samples
+pub fn infer_schema(samples: &[Message]) -> InferredMessage {
+    let mut schema = InferredMessage::default();
+    for sample in samples {
+        schema.add(sample);
+    }
+    schema
+}
+
+impl InferredMessage {
+    /// Merges the fields of a message
+    pub fn add(&mut self, message: &Message) {
+        let mut seen = BTreeSet::new();
+        for field in &message.fields {
+            let inferred = self.fields.entry(field.number).or_default();
+            if !seen.insert(field.number) {
+                inferred.repeated = true;
+            }
+            inferred.add(field);
+        }
+    }
+}
+
+impl InferredField {
+    /// Merges a value of the field
+    pub fn add(&mut self, field: &Field) {
+        *self.wire_types.entry(field.span.wire_type).or_default() += 1;
+        match &field.value {
+            FieldValue::Varint(v) => self.negative |= *v > i64::MAX as u128,
+            FieldValue::Fixed64(_) | FieldValue::Fixed32(_) => {}
+            FieldValue::Bytes([]) => self.empty += 1,
+            FieldValue::Bytes(v) => match show_as(v, &ShowAsConfig::default()) {
+                ShowAs::Bytes(_) => {
+                    self.binary += 1;
+                    if is_packed_varints(v) {
+                        self.packed += 1;
+                    }
+                }
+                _ => self.strings += 1,
+            },
+            FieldValue::Message(message) => {
+                self.messages += 1;
+                self.message.add(message);
+            }
+        }
+    }
+
+    /// The wire type seen most often. Ties go to the lower wire type.
+    pub fn main_wire_type(&self) -> u8 {
+        self.wire_types
+            .iter()
+            .max_by_key(|(wire_type, count)| (**count, std::cmp::Reverse(**wire_type)))
+            .map(|(wire_type, _)| *wire_type)
+            .unwrap_or(2)
+    }
+
+    /// The best guess for the type of the field
+    pub fn field_type(&self) -> FieldType {
+        if self.is_packed() {
+            return FieldType::PackedUint64;
+        }
+        match self.main_wire_type() {
+            0 if self.negative => FieldType::Int64,
+            0 => FieldType::Uint64,
+            1 => FieldType::Fixed64,
+            5 => FieldType::Fixed32,
+            3 => FieldType::Group,
+            _ => self.length_delimited_type(),
+        }
+    }
+
+    /// Whether the field is best declared as repeated
+    pub fn is_repeated(&self) -> bool {
+        self.repeated || self.field_type() == FieldType::PackedUint64
+    }
+
+    /// The wire types seen that do not fit the type, with their counts
+    pub fn conflicts(&self) -> Vec<(u8, usize)> {
+        let expected: &[u8] = match self.field_type() {
+            FieldType::PackedUint64 => &[0, 2],
+            _ => &[self.main_wire_type()],
+        };
+        self.wire_types
+            .iter()
+            .filter(|(wire_type, _)| !expected.contains(wire_type))
+            .map(|(wire_type, count)| (*wire_type, *count))
+            .collect()
+    }
+
+    /// Only varints and binary length-delimited values that are all valid varints.
+    /// Unpacked values of a packed field are accepted by parsers as well.
+    fn is_packed(&self) -> bool {
+        self.wire_types
+            .keys()
+            .all(|wire_type| *wire_type == 0 || *wire_type == 2)
+            && (self.wire_types.contains_key(&0) || self.binary >= MIN_PACKED_VALUES)
+            && self.binary > 0
+            && self.packed == self.binary
+            && self.strings == 0
+            && self.messages == 0
+    }
+
+    fn length_delimited_type(&self) -> FieldType {
+        let non_empty = self.messages + self.strings + self.binary;
+        if self.messages > 0 && self.messages == non_empty {
+            FieldType::Message
+        } else if self.strings > 0 && self.binary == 0 {
+            // Text is sometimes a valid serialization by chance
+            FieldType::String
+        } else {
+            FieldType::Bytes
+        }
+    }
+}
+
+fn is_packed_varints(bytes: &[u8]) -> bool {
+    let mut pos = 0;
+    while pos < bytes.len() {
+        match read_varint(&bytes[pos..]) {
+            Some((_, len)) => pos += len,
+            None => return false,
+        }
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse::ParseConfig;
+
+    fn infer(samples: &[&[u8]]) -> InferredMessage {
+        let samples: Vec<Message> = samples
+            .iter()
+            .map(|bytes| Message::parse(bytes, ParseConfig::default()).unwrap())
+            .collect();
+        infer_schema(&samples)
+    }
+
+    fn types(schema: &InferredMessage) -> Vec<(u64, FieldType, bool)> {
+        schema
+            .fields
+            .iter()
+            .map(|(number, field)| (*number, field.field_type(), field.is_repeated()))
+            .collect()
+    }
+
+    #[test]
+    fn infer_schema_works() {
+        let schema = infer(&[
+            // 1: 150, 2: "John", 3 { 1: 1 }, 3 { 1: 2 }
+            b"\x08\x96\x01\x12\x04John\x1a\x02\x08\x01\x1a\x02\x08\x02",
+            // 1: -1, 4: fixed64, 5: fixed32
+            b"\x08\xff\xff\xff\xff\xff\xff\xff\xff\xff\x01\
+              \x21\x01\x02\x03\x04\x05\x06\x07\x08\x2d\x01\x02\x03\x04",
+        ]);
+        assert_eq!(
+            types(&schema),
+            vec![
+                (1, FieldType::Int64, false),
+                (2, FieldType::String, false),
+                (3, FieldType::Message, true),
+                (4, FieldType::Fixed64, false),
+                (5, FieldType::Fixed32, false),
+            ]
+        );
+        let nested = &schema.fields[&3].message;
+        assert_eq!(types(nested), vec![(1, FieldType::Uint64, false)]);
+        assert_eq!(schema.fields[&3].messages, 2);
+
+        // 1 (group) { 2: 1 }
+        let schema = infer(&[b"\x0b\x10\x01\x0c"]);
+        assert_eq!(types(&schema), vec![(1, FieldType::Group, false)]);
+    }
+
+    #[test]
+    fn infer_schema_merges_samples() {
+        // 1: "ab" is text, the other value is a message by chance
+        let schema = infer(&[b"\x0a\x02ab", b"\x0a\x0a\x0a\x08abcdefgh"]);
+        assert_eq!(schema.fields[&1].messages, 1);
+        assert_eq!(types(&schema), vec![(1, FieldType::String, false)]);
+
+        // Empty values fit any type
+        let schema = infer(&[b"\x0a\x00", b"\x0a\x02\x08\x01"]);
+        assert_eq!(types(&schema), vec![(1, FieldType::Message, false)]);
+
+        // Binary values in some samples
+        let schema = infer(&[b"\x0a\x02ab", b"\x0a\x02\x00\xff"]);
+        assert_eq!(types(&schema), vec![(1, FieldType::Bytes, false)]);
+    }
+
+    #[test]
+    fn infer_schema_detects_packed_fields() {
+        // 1: [1, 150], 1: 3
+        let schema = infer(&[b"\x0a\x03\x01\x96\x01", b"\x08\x03"]);
+        assert_eq!(types(&schema), vec![(1, FieldType::PackedUint64, true)]);
+        assert_eq!(schema.fields[&1].conflicts(), vec![]);
+
+        let packed: &[u8] = b"\x0a\x03\x01\x96\x01";
+        let schema = infer(&[packed, packed, packed]);
+        assert_eq!(types(&schema), vec![(1, FieldType::PackedUint64, true)]);
+        // Too few values to tell from bytes
+        let schema = infer(&[packed, packed]);
+        assert_eq!(types(&schema), vec![(1, FieldType::Bytes, false)]);
+
+        // Not a valid varint sequence
+        let schema = infer(&[b"\x0a\x03\x01\x96\x01", b"\x0a\x02\x01\x96"]);
+        assert_eq!(types(&schema), vec![(1, FieldType::Bytes, false)]);
+    }
+
+    #[test]
+    fn inferred_field_reports_conflicts() {
+        // 1: 1, 1: 2, 1: fixed32
+        let schema = infer(&[b"\x08\x01", b"\x08\x02", b"\x0d\x01\x02\x03\x04"]);
+        let field = &schema.fields[&1];
+        assert_eq!(field.field_type(), FieldType::Uint64);
+        assert_eq!(field.conflicts(), vec![(5, 1)]);
+    }
+}
@@ -1,5 +1,42 @@
+//! Decoding of protobuf serializations without a schema.
+//!
+//! [`Message::decode`] turns the bytes into a tree of fields. It can be inspected directly,
+//! with [`Message::walk`] or a [`Visitor`], and rendered with a [`Formatter`] such as
+//! [`TextFormatter`], which produces the output of the `decode_raw` command line tool.
+//!
+//! ```
+//! use decode_raw::{Formatter, Message, ParseConfig, TextFormatter};
+//!
+//! // 1: 150, 2: "John"
+//! let bytes = b"\x08\x96\x01\x12\x04John";
+//! let message = Message::decode(bytes, ParseConfig::default()).unwrap();
+//! let paths: Vec<Vec<u64>> = message.walk().map(|(path, _)| path).collect();
+//! assert_eq!(paths, vec![vec![1], vec![2]]);
+//! let text = TextFormatter::default().format(&message);
+//! assert_eq!(text.lines().count(), 2);
+//! ```
+
+mod check;
+mod diff;
+mod display;
 mod filter;
+mod infer;
 mod parse;
+mod tree;
 
+pub use check::{check_canonical, Violation, ViolationKind};
+pub use diff::{diff_messages, Difference, DifferenceKind, FieldKey};
+pub use display::{
+    bytes_kinds, detect_timestamp, dotted, escape_string, format_timestamp, hexdump_rows,
+    pretty_json, show_as, spaced, strip_colors, truncate_bytes, truncate_str, BytesKind,
+    FlatFormatter, Formatter, HtmlFormatter, IndentStyle, Limits, ShowAs, ShowAsConfig,
+    TextFormatter, YamlFormatter,
+};
 pub use filter::{is_selected, SelectQuery};
-pub use parse::{try_parse_entries, Entry, EntryValue, ParseConfig};
+pub use infer::{format_proto, infer_schema, FieldType, InferredField, InferredMessage};
+pub use parse::{
+    message_confidence, parse_entries, read_fields, read_varint, scan_fields, try_parse_entries,
+    wire_type_name, Entry, EntryValue, FieldReader, FieldSpan, ParseConfig, ParseLimit,
+    StreamedField, VarintPart, WireError, WireErrorKind,
+};
+pub use tree::{Field, FieldValue, Message, Visitor, Walk};
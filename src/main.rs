@@ -1,21 +1,28 @@
 use ansi_term::Colour::{Green, Red, Yellow};
-use clap::{ArgEnum, Parser};
-use std::io::Read;
+use ansi_term::Style;
+use clap::{Parser, Subcommand};
+use decode_raw::{
+    check_canonical, diff_messages, format_proto, hexdump_rows, infer_schema, parse_entries,
+    wire_type_name, Difference, DifferenceKind, FieldKey, FieldReader, FieldSpan, FieldValue,
+    FlatFormatter, Formatter, HtmlFormatter, IndentStyle, Limits, Message, ParseConfig,
+    SelectQuery, ShowAsConfig, TextFormatter, VarintPart, Violation, ViolationKind, YamlFormatter,
+};
+use std::io::{BufRead, Read, Write};
+use std::ops::Range;
+use std::str::FromStr;
 
-mod display;
-mod filter;
-mod parse;
+mod encode;
+#[cfg(feature = "tui")]
+mod tui;
 
-use display::{dotted, escape_string, show_as, spaced, ShowAs};
-use filter::{is_selected, SelectQuery};
-use parse::{try_parse_entries, EntryValue, ParseConfig};
+use encode::{edit_fields, encode_fields, parse_text, Edit};
 
 /// Simple program to greet a person
 #[derive(Parser)]
 #[clap(author, about, version, long_about = None)]
 struct Args {
     /// How to style indent
-    #[clap(arg_enum, short, long, default_value = "dot")]
+    #[clap(short, long, default_value = "dot", possible_values = &["space", "dot", "path"])]
     indent: IndentStyle,
 
     /// Assume wire type 1 or 5 (fixed64, sfixed64, double, fixed32, sfixed32, float) is not used.
@@ -34,161 +41,657 @@ struct Args {
     #[clap(long)]
     no_fixed32: bool,
 
+    /// Nested messages deeper than this are shown as bytes
+    #[clap(long, default_value = "100")]
+    max_depth: usize,
+
+    /// The number of bytes that may be scanned in total while detecting nested messages.
+    /// When it is used up, the remaining length-delimited fields are shown as bytes.
+    #[clap(long, default_value = "1073741824")]
+    max_work: usize,
+
+    /// Length-delimited fields are only shown as nested message if the confidence that they
+    /// are one is at least this, from 0 to 1. Large field numbers, descending field order,
+    /// groups, fixed-width values and a short last field lower the confidence.
+    #[clap(long, default_value = "0.5")]
+    min_confidence: f64,
+
+    /// Length-delimited fields with larger field numbers are never shown as nested message.
+    /// Protobuf allows up to 536870911, but most schemas only use small numbers.
+    #[clap(long, default_value = "536870911")]
+    max_field_number: u64,
+
+    /// Also show the reading as message of bytes with a low confidence and
+    /// the confidence of nested messages that are not certain. The readings are
+    /// commented out with #, so encode skips them.
+    #[clap(long)]
+    show_alternatives: bool,
+
     /// Show all data in full length
     #[clap(long)]
     full: bool,
 
+    /// Maximum number of bytes to show for binary data
+    #[clap(long, default_value = "256")]
+    max_bytes: usize,
+
+    /// Maximum number of characters to show for strings. Unlimited by default.
+    #[clap(long)]
+    max_string: Option<usize>,
+
+    /// Show the first and last N bytes/characters of truncated values instead of only the first N
+    #[clap(long)]
+    keep_tail: bool,
+
+    /// Override the maximum length for a path and everything below it, e.g. .3.2=full or .4=16.
+    /// Can be used multiple times.
+    #[clap(long, multiple_occurrences(true), parse(try_from_str = parse_max_at))]
+    max_at: Vec<(SelectQuery, Option<usize>)>,
+
+    /// Annotate nested messages that look like a google.protobuf.Timestamp,
+    /// i.e. a seconds varint in a plausible range and an optional nanos varint.
+    #[clap(long)]
+    detect_timestamps: bool,
+
+    /// Pretty-print strings containing a JSON object or array
+    #[clap(long)]
+    pretty_json: bool,
+
+    /// Detect base64 and hex encoded strings and decode them as nested messages if possible
+    #[clap(long)]
+    decode_strings: bool,
+
+    /// Annotate binary data that looks like a hash (32 bytes) or UUID (16 bytes)
+    #[clap(long)]
+    detect_bytes: bool,
+
+    /// Show 20 and 32 byte values as bech32 addresses with the given prefix, e.g. cosmos
+    #[clap(long, parse(try_from_str = parse_bech32_prefix))]
+    bech32_prefix: Option<bech32::Hrp>,
+
+    /// Print a hexdump of the input in which every tag, length prefix and value
+    /// is annotated with its field path and decoded value
+    #[clap(long)]
+    annotate: bool,
+
+    /// Show the location of every field in the input
+    #[clap(long)]
+    offsets: bool,
+
+    /// Show the wire type of every field and flag varints that are longer than necessary
+    #[clap(long)]
+    show_wire_types: bool,
+
+    /// Check if the input follows the deterministic encoding rules of Cosmos SDK ADR-027.
+    /// Lists all violations and exits with a non-zero code if there are any.
+    #[clap(long)]
+    check_canonical: bool,
+
+    /// How to print the decoded message. yaml uses field numbers as keys and sequences
+    /// for repeated fields. flat prints one `path = value` line per value whose path
+    /// can be used as select path. Both always show values in full. html writes a
+    /// self-contained page with the colors of text, collapsible nested messages, all
+    /// readings of numbers as tooltip and the bytes of each field.
+    #[clap(long, default_value = "text", possible_values = &["text", "yaml", "flat", "html"], conflicts_with = "stream")]
+    output: OutputFormat,
+
+    /// Print a .proto definition of the decoded structure as starting point for a schema.
+    /// Fields are named field_N and nested messages become nested types.
+    #[clap(long, conflicts_with_all = &["annotate", "check-canonical", "stream"])]
+    emit_proto: bool,
+
+    /// Decode and print the top level fields one by one as they are read instead of
+    /// reading the whole input first. Only the current field is kept in memory.
+    #[clap(long, conflicts_with_all = &["annotate", "check-canonical"])]
+    stream: bool,
+
+    /// Explore the message in an interactive terminal UI
+    #[cfg(feature = "tui")]
+    #[clap(long, conflicts_with = "stream")]
+    tui: bool,
+
     /// The path to select. e.g. .2.1.1
     #[clap()]
     select: Option<String>,
+
+    #[clap(subcommand)]
+    command: Option<Command>,
 }
 
-#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ArgEnum)]
-enum IndentStyle {
-    Space,
-    Dot,
-    Path,
+#[derive(Subcommand)]
+enum Command {
+    /// Read the decoded text output and serialize it back to protobuf bytes.
+    /// Values must not be truncated, i.e. decode with --full. JSON and the other
+    /// --output formats cannot be read.
+    Encode,
+    /// Missing fields are created if the path exists nowhere in the message.
+    /// Missing fields are created.
+    Set {
+        /// The path of the field. e.g. .3.2
+        path: String,
+        /// The new value. A number for varint and fixed fields, text otherwise.
+        #[clap(allow_hyphen_values = true)]
+        value: String,
+    },
+    /// Remove the fields at the path and write the new serialization
+    Delete {
+        /// The path of the field. e.g. .4
+        path: String,
+    },
+    /// Compare two serialized messages field by field. Fields are matched by path and,
+    /// for repeated fields, by position. Exits with a non-zero code if they differ.
+    Diff {
+        /// The file with the old message
+        old: std::path::PathBuf,
+        /// The file with the new message
+        new: std::path::PathBuf,
+    },
+    /// Guess a schema from many samples of the same message type and print it as
+    /// .proto file. Fields are named field_N.
+    Infer {
+        /// The files with the samples
+        #[clap(required = true)]
+        samples: Vec<std::path::PathBuf>,
+        /// The name of the top level message
+        #[clap(long, default_value = "Message")]
+        name: String,
+    },
 }
 
-#[derive(Clone)]
-struct Config {
-    pub indent: IndentStyle,
-    pub select: SelectQuery,
-    pub full: bool,
-    pub parse_config: ParseConfig,
+#[derive(Clone, Copy)]
+enum OutputFormat {
+    Text,
+    Yaml,
+    Flat,
+    Html,
 }
 
-fn main() {
-    let args = Args::parse();
+impl FromStr for OutputFormat {
+    type Err = String;
 
-    let mut input = Vec::<u8>::new();
-    for byte in std::io::stdin().bytes() {
-        input.push(byte.unwrap());
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        match input {
+            "text" => Ok(OutputFormat::Text),
+            "yaml" => Ok(OutputFormat::Yaml),
+            "flat" => Ok(OutputFormat::Flat),
+            "html" => Ok(OutputFormat::Html),
+            _ => Err(format!("unknown output format '{}'", input)),
+        }
     }
+}
+
+fn parse_max_at(input: &str) -> Result<(SelectQuery, Option<usize>), String> {
+    let (path, max) = input
+        .split_once('=')
+        .ok_or_else(|| "expected <PATH>=<N> or <PATH>=full".to_string())?;
+    let max = match max {
+        "full" => None,
+        _ => Some(max.parse::<usize>().map_err(|err| err.to_string())?),
+    };
+    let path = SelectQuery::parse(path)?;
+    if path.has_indices() {
+        return Err("indices are not supported in --max-at".to_string());
+    }
+    Ok((path, max))
+}
+
+fn parse_bech32_prefix(input: &str) -> Result<bech32::Hrp, String> {
+    bech32::Hrp::parse(input).map_err(|err| err.to_string())
+}
+
+fn main() {
+    let args = Args::parse();
 
-    let config = Config {
+    let config = TextFormatter {
         indent: args.indent,
         select: SelectQuery::parse(&args.select.unwrap_or_default()).unwrap(),
-        full: args.full,
+        limits: if args.full {
+            Limits::default()
+        } else {
+            Limits {
+                max_bytes: Some(args.max_bytes),
+                max_string: args.max_string,
+                keep_tail: args.keep_tail,
+                overrides: args.max_at,
+            }
+        },
+        detect_timestamps: args.detect_timestamps,
+        pretty_json: args.pretty_json,
+        offsets: args.offsets,
+        show_wire_types: args.show_wire_types,
+        show_alternatives: args.show_alternatives,
+        show_as_config: ShowAsConfig {
+            decode_strings: args.decode_strings,
+            detect_bytes: args.detect_bytes,
+            bech32_prefix: args.bech32_prefix,
+        },
         parse_config: ParseConfig {
             no_fixed64: args.no_fixed || args.no_fixed64,
             no_fixed32: args.no_fixed || args.no_fixed32,
+            max_depth: args.max_depth,
+            max_work: args.max_work,
+            min_confidence: args.min_confidence,
+            max_field_number: args.max_field_number,
         },
     };
 
-    decode(&input, &config);
-}
-
-fn decode(bytes: &[u8], config: &Config) {
-    if let Some(entries) = try_parse_entries(bytes, config.parse_config) {
-        for entry in entries
-            .into_iter()
-            .filter(|e| is_selected(e, &config.select))
-        {
-            let stripped_path = entry.path[config.select.len()..].to_vec();
-
-            let path = print_path(&stripped_path, config);
-            match entry.value {
-                EntryValue::Fixed64(v) => println!("{}: (64 bit) {}", path, print_fixed64(v)),
-                EntryValue::Fixed32(v) => println!("{}: (32 bit) {}", path, print_fixed32(v)),
-                EntryValue::Varint(i) => println!("{}: {}", path, print_int(i)),
-                EntryValue::Bytes(v) => {
-                    print!(
-                        "{}: ({} bytes) {}\n",
-                        path,
-                        v.len(),
-                        print_bytes(&v, config.full)
-                    )
-                }
-                EntryValue::OpenNested => {
-                    if !path.is_empty() {
-                        print!("{} {{\n", path);
-                    }
-                }
-                EntryValue::CloseNested => {
-                    if !path.is_empty() {
-                        print!("{}}}\n", dotted((path.chars().count() - 1) / 2));
-                    }
-                }
-            }
+    // These match fields by their path only
+    let ignores_indices = args.check_canonical
+        || args.annotate
+        || args.stream
+        || matches!(args.command, Some(Command::Diff { .. }));
+    if config.select.has_indices() && ignores_indices {
+        eprintln!("indices in the select path are only supported when decoding");
+        std::process::exit(1);
+    }
+
+    if let Some(Command::Diff { old, new }) = &args.command {
+        let old = std::fs::read(old).unwrap();
+        let new = std::fs::read(new).unwrap();
+        if !diff(&old, &new, &config) {
+            std::process::exit(1);
         }
+        return;
+    }
+
+    if let Some(Command::Infer { samples, name }) = &args.command {
+        return infer(samples, name, &config);
+    }
+
+    if args.stream && args.command.is_none() {
+        return stream(std::io::stdin().lock(), &config);
+    }
+
+    let mut input = Vec::<u8>::new();
+    std::io::stdin().read_to_end(&mut input).unwrap();
+
+    match &args.command {
+        Some(Command::Encode) => return encode(&input),
+        Some(Command::Set { path, value }) => return edit(&input, path, Edit::Set(value)),
+        Some(Command::Delete { path }) => return edit(&input, path, Edit::Delete),
+        Some(Command::Diff { .. }) | Some(Command::Infer { .. }) | None => {}
+    }
+
+    #[cfg(feature = "tui")]
+    if args.tui {
+        if let Err(error) = tui::run(&input, config.parse_config) {
+            eprintln!("{}", error);
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    if args.check_canonical {
+        if !check(&input, &config) {
+            std::process::exit(1);
+        }
+    } else if args.emit_proto {
+        emit_proto(&input, &config);
+    } else if args.annotate {
+        annotate(&input, &config);
     } else {
-        panic!("Input bytes is not a valid protobuf serialization");
+        match args.output {
+            OutputFormat::Text => decode(&input, config.parse_config, &config),
+            OutputFormat::Yaml => {
+                let formatter = YamlFormatter {
+                    select: config.select.clone(),
+                };
+                decode(&input, config.parse_config, &formatter)
+            }
+            OutputFormat::Flat => {
+                let formatter = FlatFormatter {
+                    select: config.select.clone(),
+                };
+                decode(&input, config.parse_config, &formatter)
+            }
+            OutputFormat::Html => {
+                let formatter = HtmlFormatter {
+                    text: config.clone(),
+                    input: &input,
+                };
+                decode(&input, config.parse_config, &formatter)
+            }
+        }
     }
 }
 
-fn yellow(s: impl ToString) -> String {
-    Yellow.paint(s.to_string()).to_string()
+fn decode(bytes: &[u8], config: ParseConfig, formatter: &dyn Formatter) {
+    match Message::parse(bytes, config) {
+        Ok(message) => print!("{}", formatter.format(&message)),
+        Err(error) => panic!(
+            "Input bytes is not a valid protobuf serialization: {}",
+            error
+        ),
+    }
 }
 
-fn print_fixed64(v: [u8; 8]) -> String {
-    let as_unsigned = u64::from_le_bytes(v);
-    let as_signed = i64::from_le_bytes(v);
-    let as_float = f64::from_le_bytes(v);
-
-    let mut values = Vec::<String>::new();
-    values.push(yellow(as_unsigned));
-    if as_signed < 0 {
-        values.push(yellow(as_signed));
+/// Prints the structure of the message as .proto file
+fn emit_proto(bytes: &[u8], config: &TextFormatter) {
+    match Message::parse(bytes, config.parse_config) {
+        Ok(message) => print!("{}", format_proto(&infer_schema(&[message]), "Unknown")),
+        Err(error) => panic!(
+            "Input bytes is not a valid protobuf serialization: {}",
+            error
+        ),
     }
-    values.push(yellow(as_float));
-    values.join(" / ")
 }
 
-fn print_fixed32(v: [u8; 4]) -> String {
-    let as_unsigned = u32::from_le_bytes(v);
-    let as_signed = i32::from_le_bytes(v);
-    let as_float = f32::from_le_bytes(v);
+/// Decodes and prints the top level fields as they are read
+fn stream(input: impl BufRead, config: &TextFormatter) {
+    let mut reader = FieldReader::new(input);
+    let mut stdout = std::io::stdout().lock();
+    loop {
+        let field = match reader.next_field() {
+            Ok(Some(field)) => field,
+            Ok(None) => return,
+            Err(error) => panic!(
+                "Input bytes is not a valid protobuf serialization: {}",
+                error
+            ),
+        };
+        match Message::parse_at(field.bytes, config.parse_config, field.offset) {
+            Ok(message) => write!(stdout, "{}", config.format(&message)).unwrap(),
+            Err(error) => panic!(
+                "Input bytes is not a valid protobuf serialization: {}",
+                error
+            ),
+        }
+    }
+}
 
-    let mut values = Vec::<String>::new();
-    values.push(yellow(as_unsigned));
-    if as_signed < 0 {
-        values.push(yellow(as_signed));
+fn encode(text: &[u8]) {
+    let fields = std::str::from_utf8(text)
+        .map_err(|err| err.to_string())
+        .and_then(parse_text);
+    match fields {
+        Ok(fields) => std::io::stdout()
+            .write_all(&encode_fields(&fields))
+            .unwrap(),
+        Err(err) => {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        }
     }
-    values.push(yellow(as_float));
-    values.join(" / ")
 }
 
-fn print_int(i: impl Into<u128>) -> String {
-    Red.paint(i.into().to_string()).to_string()
+fn edit(bytes: &[u8], path: &str, edit: Edit) {
+    let edited = SelectQuery::parse(path).and_then(|path| {
+        if path.has_indices() {
+            return Err("indices are not supported in the path".to_string());
+        }
+        edit_fields(bytes, &path, edit)
+    });
+    match edited {
+        Ok(edited) => std::io::stdout().write_all(&edited).unwrap(),
+        Err(err) => {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        }
+    }
 }
 
-fn print_bytes(bytes: &[u8], full: bool) -> String {
-    let text = match show_as(bytes) {
-        ShowAs::String(s) => escape_string(s),
-        ShowAs::Bytes(bytes) => {
-            const MAX_BYTES: usize = 256;
-            if full || bytes.len() <= MAX_BYTES {
-                hex::encode(bytes)
+/// Prints all violations of the deterministic encoding rules and returns true
+/// if there are none.
+fn check(bytes: &[u8], config: &TextFormatter) -> bool {
+    match parse_entries(bytes, config.parse_config) {
+        Ok(entries) => {
+            let violations: Vec<Violation> = check_canonical(&entries)
+                .into_iter()
+                .filter(|v| v.path.starts_with(&config.select))
+                .collect();
+            for violation in &violations {
+                let path: String = violation.path.iter().map(|n| format!(".{}", n)).collect();
+                println!(
+                    "{} @{}: {}",
+                    path,
+                    violation.offset,
+                    Red.paint(violation.kind.to_string())
+                );
+            }
+            if violations.is_empty() {
+                println!("{}", Green.paint("Encoding is canonical"));
             } else {
-                let mut truncated = hex::encode(&bytes[0..MAX_BYTES]);
-                truncated.push('…');
-                truncated
+                println!("{}", print_violation_summary(&violations));
             }
+            violations.is_empty()
+        }
+        Err(error) => {
+            eprintln!(
+                "Input bytes is not a valid protobuf serialization: {}",
+                error
+            );
+            std::process::exit(1);
         }
+    }
+}
+
+/// Counts the violations per kind, e.g. "3 violations (2 field order, 1 default value)"
+fn print_violation_summary(violations: &[Violation]) -> String {
+    let mut field_order = 0;
+    let mut default_value = 0;
+    let mut non_minimal = 0;
+    let mut not_packed = 0;
+    for violation in violations {
+        match violation.kind {
+            ViolationKind::FieldOrder { .. } => field_order += 1,
+            ViolationKind::DefaultValue => default_value += 1,
+            ViolationKind::NonMinimalVarint(_) => non_minimal += 1,
+            ViolationKind::NotPacked => not_packed += 1,
+        }
+    }
+    let counts: Vec<String> = [
+        (field_order, "field order"),
+        (default_value, "default value"),
+        (non_minimal, "non-minimal varint"),
+        (not_packed, "not packed"),
+    ]
+    .iter()
+    .filter(|(count, _)| *count != 0)
+    .map(|(count, name)| format!("{} {}", count, name))
+    .collect();
+    let noun = if violations.len() == 1 {
+        "violation"
+    } else {
+        "violations"
+    };
+    format!("{} {} ({})", violations.len(), noun, counts.join(", "))
+}
+
+/// Prints the differences between the two messages and returns true if there are none
+fn diff(old: &[u8], new: &[u8], config: &TextFormatter) -> bool {
+    let parse = |bytes| {
+        Message::parse(bytes, config.parse_config).unwrap_or_else(|error| {
+            panic!(
+                "Input bytes is not a valid protobuf serialization: {}",
+                error
+            )
+        })
     };
-    Green.paint(text).to_string()
+    let differences: Vec<Difference> = diff_messages(&parse(old), &parse(new))
+        .into_iter()
+        .filter(|d| d.numbers().starts_with(&config.select))
+        .collect();
+    for difference in &differences {
+        println!("{}", print_difference(difference, config));
+    }
+    differences.is_empty()
 }
 
-fn print_path(path: &[u64], config: &Config) -> String {
-    match config.indent {
-        IndentStyle::Dot => {
-            let mut out = dotted(path.len().saturating_sub(1));
-            if let Some(last) = path.last() {
-                out.push_str(&format!("{}", last));
+/// Prints the schema inferred from the sample files
+fn infer(paths: &[std::path::PathBuf], name: &str, config: &TextFormatter) {
+    let inputs: Vec<Vec<u8>> = paths
+        .iter()
+        .map(|path| std::fs::read(path).unwrap())
+        .collect();
+    let mut samples = Vec::new();
+    for (path, bytes) in paths.iter().zip(&inputs) {
+        match Message::parse(bytes, config.parse_config) {
+            Ok(message) => samples.push(message),
+            Err(error) => {
+                eprintln!(
+                    "{}: not a valid protobuf serialization: {}",
+                    path.display(),
+                    error
+                );
+                std::process::exit(1);
             }
-            out
         }
-        IndentStyle::Space => {
-            let mut out = spaced(path.len().saturating_sub(1));
-            if let Some(last) = path.last() {
-                out.push_str(&format!("{}", last));
+    }
+    print!("{}", format_proto(&infer_schema(&samples), name));
+}
+
+fn print_difference(difference: &Difference, config: &TextFormatter) -> String {
+    let path = print_field_keys(&difference.path);
+    let numbers = difference.numbers();
+    match &difference.kind {
+        DifferenceKind::Added(value) => format!(
+            "{} {}: {}",
+            Green.paint("+"),
+            path,
+            config.format_value(value, &numbers)
+        ),
+        DifferenceKind::Removed(value) => format!(
+            "{} {}: {}",
+            Red.paint("-"),
+            path,
+            config.format_value(value, &numbers)
+        ),
+        DifferenceKind::Changed { old, new } => format!(
+            "{} {}: {} → {}",
+            Yellow.paint("~"),
+            path,
+            config.format_value(old, &numbers),
+            config.format_value(new, &numbers)
+        ),
+        DifferenceKind::VarintLength { part, old, new } => format!(
+            "{} {}: {}",
+            Style::new().dimmed().paint("="),
+            path,
+            Style::new().dimmed().paint(format!(
+                "same value but {} varint has {} instead of {} bytes",
+                part.name(),
+                new,
+                old
+            ))
+        ),
+        DifferenceKind::FieldOrder => format!(
+            "{} {}: {}",
+            Style::new().dimmed().paint("="),
+            path,
+            Style::new()
+                .dimmed()
+                .paint("same fields but in a different order")
+        ),
+        DifferenceKind::WireType { old, new } => format!(
+            "{} {}: {}",
+            Style::new().dimmed().paint("="),
+            path,
+            Style::new().dimmed().paint(format!(
+                "same message but encoded as {} instead of {}",
+                print_message_encoding(*new),
+                print_message_encoding(*old)
+            ))
+        ),
+    }
+}
+
+/// How a nested message with the given wire type is encoded
+fn print_message_encoding(wire_type: u8) -> &'static str {
+    match wire_type {
+        3 => "group",
+        _ => "length-delimited",
+    }
+}
+
+fn print_field_keys(path: &[FieldKey]) -> String {
+    if path.is_empty() {
+        return ".".to_string();
+    }
+    path.iter().map(|key| key.to_string()).collect()
+}
+
+fn annotate(bytes: &[u8], config: &TextFormatter) {
+    match Message::parse(bytes, config.parse_config) {
+        Ok(message) => {
+            let mut rows = Vec::new();
+            for (field_path, field) in message.walk() {
+                if !field_path.starts_with(&config.select) {
+                    continue;
+                }
+                let path: String = field_path.iter().map(|n| format!(".{}", n)).collect();
+                let span = &field.span;
+                rows.extend(header_annotations(span, &path));
+                match &field.value {
+                    // The payload rows are printed by the fields of the nested message
+                    FieldValue::Message(_) => {}
+                    FieldValue::Varint(_) => rows.push((
+                        span.payload.clone(),
+                        format!(
+                            "{} = {}{}",
+                            path,
+                            config.format_value(&field.value, &field_path),
+                            print_overlong_part(span, VarintPart::Value)
+                        ),
+                    )),
+                    value => rows.push((
+                        span.payload.clone(),
+                        format!("{} = {}", path, config.format_value(value, &field_path)),
+                    )),
+                }
+                if span.end_tag_len != 0 {
+                    rows.push((
+                        span.payload.end..span.end(),
+                        format!("{} end group tag", path),
+                    ));
+                }
+            }
+            // End group tags follow the fields of the group, which are walked later
+            rows.sort_by_key(|(range, _)| range.start);
+            for (range, annotation) in rows {
+                for row in hexdump_rows(range.start, &bytes[range], &annotation) {
+                    println!("{}", row);
+                }
             }
-            out
-        }
-        IndentStyle::Path => {
-            let formated_path: String = path.iter().map(|number| format!(".{}", number)).collect();
-            formated_path
         }
+        Err(error) => panic!(
+            "Input bytes is not a valid protobuf serialization: {}",
+            error
+        ),
+    }
+}
+
+/// The byte ranges and annotations of the tag and length prefix of a field
+fn header_annotations(span: &FieldSpan, path: &str) -> Vec<(Range<usize>, String)> {
+    let value_start = span.start + span.tag_len;
+    let mut out = vec![(
+        span.start..value_start,
+        format!(
+            "{} tag: field {}, {}{}",
+            path,
+            span.number,
+            wire_type_name(span.wire_type),
+            print_overlong_part(span, VarintPart::Tag)
+        ),
+    )];
+    if span.length_len != 0 {
+        out.push((
+            value_start..span.payload.start,
+            format!(
+                "{} length: {}{}",
+                path,
+                span.payload.len(),
+                print_overlong_part(span, VarintPart::Length)
+            ),
+        ));
+    }
+    out
+}
+
+/// A marker for the given varint of the field if it is longer than necessary
+fn print_overlong_part(span: &FieldSpan, part: VarintPart) -> String {
+    if span.overlong.contains(&part) {
+        format!(" {}", Red.bold().paint("[non-minimal]"))
+    } else {
+        String::new()
     }
 }
@@ -0,0 +1,137 @@
+//! Estimating if the payload of a length-delimited field is a nested message.
+//!
+//! Strings and binary data such as hashes are often a valid serialization by chance.
+//! Such accidental messages tend to have properties that are rare in real ones,
+//! which are penalized here.
+
+use std::ops::RangeInclusive;
+
+use super::wire::FieldSpan;
+
+/// The largest field number protobuf allows
+pub const MAX_FIELD_NUMBER: u64 = (1 << 29) - 1;
+
+/// Checks if protobuf allows the field number, i.e. it is between 1 and [`MAX_FIELD_NUMBER`]
+pub fn is_valid_field_number(number: u64) -> bool {
+    (1..=MAX_FIELD_NUMBER).contains(&number)
+}
+
+/// Field numbers reserved for the protobuf implementation
+pub const RESERVED_FIELD_NUMBERS: RangeInclusive<u64> = 19000..=19999;
+
+/// Returns how likely the fields are a real message rather than bytes that
+/// happen to be a valid serialization, from 0 to 1.
+///
+/// Each kind of anomaly is penalized once, however many fields show it,
+/// so long messages do not score lower than short ones.
+pub fn message_confidence(fields: &[FieldSpan]) -> f64 {
+    let mut long_tag: f64 = 1.0;
+    let mut reserved = false;
+    let mut descents = 0;
+    let mut group = false;
+    let mut fixed = false;
+    let mut empty = false;
+    let mut previous = 0;
+    for field in fields {
+        // Schemas rarely need tags longer than two bytes, but random bytes with
+        // continuation bits produce them easily
+        long_tag = long_tag.min(match field.number {
+            0..=2047 => 1.0,
+            2048..=262_143 => 0.7,
+            _ => 0.3,
+        });
+        reserved |= RESERVED_FIELD_NUMBERS.contains(&field.number);
+        // Fields are usually serialized in ascending order
+        if field.number < previous {
+            descents += 1;
+        }
+        previous = field.number;
+        // Groups are deprecated and fixed-width values are less common than varints
+        // and length-delimited values
+        match field.wire_type {
+            3 => group = true,
+            1 | 5 => fixed = true,
+            2 if field.payload.is_empty() => empty = true,
+            _ => {}
+        }
+    }
+    // Merged messages restart the order now and then, but a message that
+    // descends more often than not is unlikely to have been written by a schema
+    let mut confidence = long_tag;
+    if descents > 1 && descents * 2 > fields.len() - 1 {
+        confidence *= 0.5;
+    } else if descents > 0 {
+        confidence *= 0.8;
+    }
+    for (found, factor) in [(reserved, 0.2), (group, 0.5), (fixed, 0.9), (empty, 0.9)] {
+        if found {
+            confidence *= factor;
+        }
+    }
+    // Random bytes often end in a short field that covers whatever is left
+    if let [.., before, last] = fields {
+        if last.end() - last.start <= 2 && before.end() - before.start > 2 {
+            confidence *= 0.8;
+        }
+    }
+    confidence
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse::read_fields;
+
+    fn confidence(bytes: &[u8]) -> f64 {
+        message_confidence(&read_fields(bytes).unwrap())
+    }
+
+    #[test]
+    fn message_confidence_accepts_typical_messages() {
+        // 1: 150, 2: "John", 3 { 1: 1 }
+        assert_eq!(confidence(b"\x08\x96\x01\x12\x04John\x1a\x02\x08\x01"), 1.0);
+        // Repeated fields
+        assert_eq!(confidence(b"\x08\x01\x08\x02\x08\x03"), 1.0);
+        assert_eq!(confidence(b""), 1.0);
+    }
+
+    #[test]
+    fn message_confidence_penalizes_each_anomaly_once() {
+        // 1: 1.0, 2: 1.0, ..., 7: 1.0 as doubles
+        let mut doubles = Vec::new();
+        for number in 1..=7u8 {
+            doubles.push(number << 3 | 1);
+            doubles.extend(1f64.to_le_bytes());
+        }
+        assert_eq!(confidence(&doubles), 0.9);
+        // 2048: 1, 2049: 1, 2050: 1
+        assert_eq!(
+            confidence(b"\x80\x80\x01\x01\x88\x80\x01\x01\x90\x80\x01\x01"),
+            0.7
+        );
+        // A single restart of the order is as likely as several in a long message
+        assert_eq!(confidence(b"\x08\x01\x10\x01\x08\x01\x10\x01\x08\x01"), 0.8);
+    }
+
+    #[test]
+    fn message_confidence_penalizes_unusual_fields() {
+        // Field 300000
+        assert!(confidence(b"\x80\xcf\x92\x01\x01") < 0.5);
+        // Descending order
+        assert_eq!(confidence(b"\x10\x01\x08\x01"), 0.8);
+        // Group and fixed32
+        assert_eq!(confidence(b"\x0b\x0c\x15\x00\x00\x00\x00"), 0.5 * 0.9);
+        // Reserved field number
+        assert_eq!(confidence(b"\xc0\xb8\x09\x01"), 0.7 * 0.2);
+        // Short last field after a longer one
+        assert_eq!(confidence(b"\x12\x04John\x18\x01"), 0.8);
+        // Hashes that happen to be valid serializations
+        let hashes = [
+            "30bef3ee80d750103175f44b825869b0d230cdc1adced509dbd600fcaa8abe0e",
+            "1842b8aae28efb2adcd0ece9a9d4fa04d8fa82812ae71f193cbd1280ac468f4e",
+        ];
+        for hash in hashes {
+            assert!(confidence(&hex::decode(hash).unwrap()) < 0.5);
+        }
+    }
+}
@@ -1,3 +1,15 @@
+mod confidence;
 mod proto;
+mod stream;
+mod wire;
 
-pub use proto::{try_parse_entries, Entry, EntryValue, ParseConfig};
+pub use confidence::{is_valid_field_number, message_confidence};
+pub use proto::{
+    direct_fields, parse_entries, try_parse_entries, Entry, EntryValue, ParseConfig, ParseLimit,
+};
+pub(crate) use proto::{is_likely_message, parse_entries_at};
+pub use stream::{FieldReader, StreamedField};
+pub use wire::{
+    read_fields, read_varint, scan_fields, wire_type_name, FieldSpan, VarintPart, WireError,
+    WireErrorKind,
+};
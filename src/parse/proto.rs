@@ -1,15 +1,30 @@
-use protofish::decode::UnknownValue;
-use protofish::prelude::*;
-use protofish::prelude::{Context, FieldValue};
+use std::fmt;
 
+use super::confidence::{is_valid_field_number, message_confidence, MAX_FIELD_NUMBER};
+use super::wire::{read_fields, read_varint, FieldSpan, WireError, WireErrorKind};
+
+/// A field or the start or end of a nested message.
+///
+/// Byte values borrow from the parsed input.
 #[derive(Debug, PartialEq)]
-pub struct Entry {
+pub struct Entry<'a> {
     pub path: Vec<u64>,
-    pub value: EntryValue,
+    pub value: EntryValue<'a>,
+    /// The location of the field in the input bytes.
+    ///
+    /// For `OpenNested` and `CloseNested` this is the location of the field
+    /// containing the nested message.
+    pub span: FieldSpan,
+    /// Set for bytes that were not tried as nested message because a limit was reached
+    pub limit: Option<ParseLimit>,
+    /// For length-delimited fields that are a valid serialization: how likely
+    /// the payload is a message, see `message_confidence`. Payloads below
+    /// `ParseConfig::min_confidence` are bytes.
+    pub confidence: Option<f64>,
 }
 
-#[derive(Debug, PartialEq)]
-pub enum EntryValue {
+#[derive(Debug, PartialEq, Clone)]
+pub enum EntryValue<'a> {
     /// Wire type 1 (64-bit value) used for fixed64, sfixed64, double.
     ///
     /// The inner value here contains the bytes in the original order from the serialization.
@@ -25,7 +40,7 @@ pub enum EntryValue {
     /// Wire type 0 (Varint) used for int32, int64, uint32, uint64, sint32, sint64, bool, enum
     Varint(u128),
     /// Wire type 2 (length delimited).
-    Bytes(Vec<u8>),
+    Bytes(&'a [u8]),
     OpenNested,
     CloseNested,
 }
@@ -34,104 +49,277 @@ pub enum EntryValue {
 pub struct ParseConfig {
     pub no_fixed64: bool,
     pub no_fixed32: bool,
+    /// Nested messages deeper than this are shown as bytes
+    pub max_depth: usize,
+    /// The number of bytes that may be scanned in total while trying to parse
+    /// length-delimited fields as nested messages. When it is used up, all remaining
+    /// length-delimited fields are shown as bytes.
+    pub max_work: usize,
+    /// Length-delimited fields are only shown as nested message if
+    /// their `message_confidence` is at least this
+    pub min_confidence: f64,
+    /// Payloads with larger field numbers are never nested messages.
+    /// Can be lowered for schemas that only use small numbers.
+    pub max_field_number: u64,
 }
 
 impl Default for ParseConfig {
     fn default() -> Self {
-        Self {
+        ParseConfig {
             no_fixed64: false,
             no_fixed32: false,
+            // The recursion limit of the protobuf implementations
+            max_depth: 100,
+            max_work: 1 << 30,
+            min_confidence: 0.5,
+            max_field_number: MAX_FIELD_NUMBER,
+        }
+    }
+}
+
+/// A limit of the `ParseConfig` that stopped the detection of nested messages
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum ParseLimit {
+    Depth,
+    Work,
+}
+
+impl fmt::Display for ParseLimit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseLimit::Depth => write!(f, "not decoded, max depth reached"),
+            ParseLimit::Work => write!(f, "not decoded, work budget used up"),
         }
     }
 }
 
 /// Tries to parse bytes as protobuf message and returns entries.
 /// Each entry represents one line in the output.
-pub fn try_parse_entries(bytes: &[u8], config: ParseConfig) -> Option<Vec<Entry>> {
-    try_parse_entries_inner(bytes, config, &[])
+pub fn try_parse_entries(bytes: &[u8], config: ParseConfig) -> Option<Vec<Entry<'_>>> {
+    try_parse_entries_inner(bytes, config, &[], 0)
+}
+
+/// Parses bytes as protobuf message like `try_parse_entries` but reports why
+/// they are not a valid serialization. Empty bytes are an empty message.
+pub fn parse_entries(bytes: &[u8], config: ParseConfig) -> Result<Vec<Entry<'_>>, WireError> {
+    parse_entries_at(bytes, config, 0)
+}
+
+/// Like `parse_entries` for bytes found at the offset of a larger input
+pub(crate) fn parse_entries_at(
+    bytes: &[u8],
+    config: ParseConfig,
+    offset: usize,
+) -> Result<Vec<Entry<'_>>, WireError> {
+    let mut out = Vec::new();
+    let mut work = config.max_work;
+    parse_fields(bytes, config, &[], offset, &mut work, &mut out)?;
+    Ok(out)
 }
 
 /// The implementation for try_parse_entries.
 ///
-/// The extra path argument is the position in the larger structure
+/// The extra path and offset arguments are the position in the larger structure
 /// where the currently expected bytes were found. This is required
-/// to be able return the absolute path in the resulting entry.
-fn try_parse_entries_inner(bytes: &[u8], config: ParseConfig, path: &[u64]) -> Option<Vec<Entry>> {
+/// to be able return the absolute path and location in the resulting entry.
+fn try_parse_entries_inner<'a>(
+    bytes: &'a [u8],
+    config: ParseConfig,
+    path: &[u64],
+    offset: usize,
+) -> Option<Vec<Entry<'a>>> {
     if bytes.is_empty() {
         // Empty byte arrays should be represented as "" instead of empty message
         return None;
     }
+    let mut out = Vec::new();
+    let mut work = config.max_work;
+    parse_fields(bytes, config, path, offset, &mut work, &mut out).ok()?;
+    Some(out)
+}
 
-    let fields = decode_fields(&bytes);
-    let mut out = Vec::<Entry>::new();
-    for field in fields.into_iter() {
+/// Appends the entries of all fields in the bytes to `out`. Length-delimited fields
+/// become nested messages if their payload can be parsed as one, otherwise bytes.
+///
+/// `work` is the remaining number of bytes that may be scanned for nested messages.
+fn parse_fields<'a>(
+    bytes: &'a [u8],
+    config: ParseConfig,
+    path: &[u64],
+    offset: usize,
+    work: &mut usize,
+    out: &mut Vec<Entry<'a>>,
+) -> Result<(), WireError> {
+    let spans = read_fields(bytes).map_err(|err| err.shifted(offset))?;
+    parse_spans(bytes, spans, config, path, offset, work, out)
+}
+
+/// Like `parse_fields` for the fields already read from the bytes
+fn parse_spans<'a>(
+    bytes: &'a [u8],
+    spans: Vec<FieldSpan>,
+    config: ParseConfig,
+    path: &[u64],
+    offset: usize,
+    work: &mut usize,
+    out: &mut Vec<Entry<'a>>,
+) -> Result<(), WireError> {
+    for span in spans {
         let mut nested_path = path.to_vec();
-        nested_path.push(field.number);
+        nested_path.push(span.number);
+        let payload = &bytes[span.payload.clone()];
+        let span = span.shifted(offset);
+        let excluded = |wire_type: u8| WireError {
+            offset: span.start,
+            kind: WireErrorKind::ExcludedWireType(wire_type),
+        };
+        let limit = match span.wire_type {
+            2 if payload.is_empty() => None,
+            2 | 3 => check_limits(config, nested_path.len(), payload.len(), work),
+            _ => None,
+        };
+        let mut confidence = None;
 
-        match &field.value {
-            Value::Unknown(unknown) => match unknown {
-                UnknownValue::Fixed64(v) => {
-                    if config.no_fixed64 {
-                        return None;
-                    }
-                    out.push(Entry {
-                        path: nested_path,
-                        value: EntryValue::Fixed64(v.to_le_bytes()),
-                    })
-                }
-                UnknownValue::Fixed32(v) => {
-                    if config.no_fixed32 {
-                        return None;
-                    }
-                    out.push(Entry {
-                        path: nested_path,
-                        value: EntryValue::Fixed32(v.to_le_bytes()),
-                    })
-                }
-                UnknownValue::Varint(v) => out.push(Entry {
-                    path: nested_path,
-                    value: EntryValue::Varint(*v),
-                }),
-                UnknownValue::VariableLength(v) => {
-                    if let Some(nested_entries) = try_parse_entries_inner(v, config, &nested_path) {
+        let value = match span.wire_type {
+            0 => EntryValue::Varint(read_varint(payload).unwrap().0),
+            1 if config.no_fixed64 => return Err(excluded(1)),
+            1 => EntryValue::Fixed64(payload.try_into().unwrap()),
+            5 if config.no_fixed32 => return Err(excluded(5)),
+            5 => EntryValue::Fixed32(payload.try_into().unwrap()),
+            2 | 3 if limit.is_some() => EntryValue::Bytes(payload),
+            2 => match read_fields(payload) {
+                Ok(nested) if !nested.is_empty() && has_valid_numbers(&nested, config) => {
+                    let score = message_confidence(&nested);
+                    confidence = Some(score);
+                    if score < config.min_confidence {
+                        EntryValue::Bytes(payload)
+                    } else {
+                        // Parse into `out` directly and roll back if the payload is not a message
+                        let open = out.len();
                         out.push(Entry {
                             path: nested_path.clone(),
                             value: EntryValue::OpenNested,
+                            span: span.clone(),
+                            limit: None,
+                            confidence,
                         });
-                        out.extend(nested_entries);
-                        out.push(Entry {
-                            path: nested_path,
-                            value: EntryValue::CloseNested,
-                        });
-                    } else {
-                        out.push(Entry {
-                            path: nested_path,
-                            value: EntryValue::Bytes(v.to_vec()),
-                        })
+                        let nested_offset = span.payload.start;
+                        match parse_spans(
+                            payload,
+                            nested,
+                            config,
+                            &nested_path,
+                            nested_offset,
+                            work,
+                            out,
+                        ) {
+                            Ok(()) => EntryValue::CloseNested,
+                            Err(_) => {
+                                out.truncate(open);
+                                confidence = None;
+                                EntryValue::Bytes(payload)
+                            }
+                        }
                     }
                 }
-                UnknownValue::Invalid(_wire_type, _bytes) => {
-                    return None;
-                }
+                _ => EntryValue::Bytes(payload),
             },
-            _ => return None,
+            // Groups are always messages, so errors in them make the whole message invalid
+            3 => {
+                out.push(Entry {
+                    path: nested_path.clone(),
+                    value: EntryValue::OpenNested,
+                    span: span.clone(),
+                    limit: None,
+                    confidence: None,
+                });
+                parse_fields(payload, config, &nested_path, span.payload.start, work, out)?;
+                EntryValue::CloseNested
+            }
+            _ => unreachable!("read_fields only returns fields with a value"),
         };
+        out.push(Entry {
+            path: nested_path,
+            value,
+            span,
+            limit,
+            confidence,
+        });
     }
-    Some(out)
+    Ok(())
+}
+
+/// Checks if all field numbers are in the range allowed by protobuf and the config
+fn has_valid_numbers(fields: &[FieldSpan], config: ParseConfig) -> bool {
+    fields
+        .iter()
+        .all(|field| is_valid_field_number(field.number) && field.number <= config.max_field_number)
+}
+
+/// Checks if fields read from bytes that are not a length-delimited payload,
+/// like a decoded hex string, are likely a message under the given config
+pub(crate) fn is_likely_message(fields: &[FieldSpan], config: ParseConfig) -> bool {
+    !fields.is_empty()
+        && has_valid_numbers(fields, config)
+        && message_confidence(fields) >= config.min_confidence
 }
 
-pub fn decode_fields(bytes: &[u8]) -> Vec<FieldValue> {
-    let context = Context::parse(&[r#"
-        syntax = "proto3";
-        package Proto;
+/// Checks if a nested message at the given depth may be parsed and
+/// takes its size from the remaining work
+fn check_limits(
+    config: ParseConfig,
+    depth: usize,
+    len: usize,
+    work: &mut usize,
+) -> Option<ParseLimit> {
+    if depth > config.max_depth {
+        return Some(ParseLimit::Depth);
+    }
+    if len > *work {
+        // Stop for all following fields, even if they are smaller
+        *work = 0;
+        return Some(ParseLimit::Work);
+    }
+    *work -= len;
+    None
+}
 
-        message Empty { }
-    "#])
-    .unwrap();
+/// Finds the fields of the message. Returns the index of each field and for
+/// nested messages the index of the corresponding `CloseNested`.
+pub fn direct_fields(entries: &[Entry]) -> Vec<(usize, usize)> {
+    let mut out = Vec::new();
+    let mut index = 0;
+    while index < entries.len() {
+        match entries[index].value {
+            EntryValue::OpenNested => {
+                let end = closing_index(entries, index);
+                out.push((index, end));
+                index = end + 1;
+            }
+            _ => {
+                out.push((index, index));
+                index += 1;
+            }
+        }
+    }
+    out
+}
 
-    let request = context.get_message("Proto.Empty").unwrap();
-    let value = request.decode(bytes, &context);
-    value.fields
+fn closing_index(entries: &[Entry], open: usize) -> usize {
+    let mut depth = 0;
+    for (index, entry) in entries.iter().enumerate().skip(open) {
+        match entry.value {
+            EntryValue::OpenNested => depth += 1,
+            EntryValue::CloseNested => {
+                depth -= 1;
+                if depth == 0 {
+                    return index;
+                }
+            }
+            _ => {}
+        }
+    }
+    panic!("Missing CloseNested entry");
 }
 
 #[cfg(test)]
@@ -146,7 +334,19 @@ mod tests {
             entries,
             &[Entry {
                 path: vec![2],
-                value: EntryValue::Bytes(b"Unknown".to_vec())
+                value: EntryValue::Bytes(b"Unknown"),
+                span: FieldSpan {
+                    number: 2,
+                    wire_type: 2,
+                    start: 0,
+                    tag_len: 1,
+                    length_len: 1,
+                    payload: 2..9,
+                    end_tag_len: 0,
+                    overlong: vec![],
+                },
+                limit: None,
+                confidence: None,
             }]
         );
 
@@ -158,11 +358,35 @@ mod tests {
             &[
                 Entry {
                     path: vec![2],
-                    value: EntryValue::Bytes(b"Unknown".to_vec())
+                    value: EntryValue::Bytes(b"Unknown"),
+                    span: FieldSpan {
+                        number: 2,
+                        wire_type: 2,
+                        start: 0,
+                        tag_len: 1,
+                        length_len: 1,
+                        payload: 2..9,
+                        end_tag_len: 0,
+                        overlong: vec![],
+                    },
+                    limit: None,
+                    confidence: None,
                 },
                 Entry {
                     path: vec![2],
-                    value: EntryValue::Bytes(b"Unknown".to_vec())
+                    value: EntryValue::Bytes(b"Unknown"),
+                    span: FieldSpan {
+                        number: 2,
+                        wire_type: 2,
+                        start: 9,
+                        tag_len: 1,
+                        length_len: 1,
+                        payload: 11..18,
+                        end_tag_len: 0,
+                        overlong: vec![],
+                    },
+                    limit: None,
+                    confidence: None,
                 }
             ]
         );
@@ -187,12 +411,24 @@ mod tests {
     fn try_parse_entries_inner_works() {
         // one
         let entries =
-            try_parse_entries_inner(b"\x12\x07Unknown", ParseConfig::default(), &[]).unwrap();
+            try_parse_entries_inner(b"\x12\x07Unknown", ParseConfig::default(), &[], 0).unwrap();
         assert_eq!(
             entries,
             &[Entry {
                 path: vec![2],
-                value: EntryValue::Bytes(b"Unknown".to_vec())
+                value: EntryValue::Bytes(b"Unknown"),
+                span: FieldSpan {
+                    number: 2,
+                    wire_type: 2,
+                    start: 0,
+                    tag_len: 1,
+                    length_len: 1,
+                    payload: 2..9,
+                    end_tag_len: 0,
+                    overlong: vec![],
+                },
+                limit: None,
+                confidence: None,
             }]
         );
 
@@ -201,6 +437,7 @@ mod tests {
             b"\x12\x07Unknown\x12\x07Unknown",
             ParseConfig::default(),
             &[],
+            0,
         )
         .unwrap();
         assert_eq!(
@@ -208,23 +445,59 @@ mod tests {
             &[
                 Entry {
                     path: vec![2],
-                    value: EntryValue::Bytes(b"Unknown".to_vec())
+                    value: EntryValue::Bytes(b"Unknown"),
+                    span: FieldSpan {
+                        number: 2,
+                        wire_type: 2,
+                        start: 0,
+                        tag_len: 1,
+                        length_len: 1,
+                        payload: 2..9,
+                        end_tag_len: 0,
+                        overlong: vec![],
+                    },
+                    limit: None,
+                    confidence: None,
                 },
                 Entry {
                     path: vec![2],
-                    value: EntryValue::Bytes(b"Unknown".to_vec())
+                    value: EntryValue::Bytes(b"Unknown"),
+                    span: FieldSpan {
+                        number: 2,
+                        wire_type: 2,
+                        start: 9,
+                        tag_len: 1,
+                        length_len: 1,
+                        payload: 11..18,
+                        end_tag_len: 0,
+                        overlong: vec![],
+                    },
+                    limit: None,
+                    confidence: None,
                 }
             ]
         );
 
         // nested path
         let entries =
-            try_parse_entries_inner(b"\x12\x07Unknown", ParseConfig::default(), &[42]).unwrap();
+            try_parse_entries_inner(b"\x12\x07Unknown", ParseConfig::default(), &[42], 0).unwrap();
         assert_eq!(
             entries,
             &[Entry {
                 path: vec![42, 2],
-                value: EntryValue::Bytes(b"Unknown".to_vec())
+                value: EntryValue::Bytes(b"Unknown"),
+                span: FieldSpan {
+                    number: 2,
+                    wire_type: 2,
+                    start: 0,
+                    tag_len: 1,
+                    length_len: 1,
+                    payload: 2..9,
+                    end_tag_len: 0,
+                    overlong: vec![],
+                },
+                limit: None,
+                confidence: None,
             }]
         );
 
@@ -233,12 +506,275 @@ mod tests {
             b"\x12\x07Unknown\x0a\x0fAtlantic ",
             ParseConfig::default(),
             &[],
+            0,
         );
         assert_eq!(res, None);
 
         // No valid protobuf (wrong wire type)
         // End group (deprecated) in field 2: hex((2 << 3) | 4)
-        let res = try_parse_entries_inner(b"\x14\x07Unknown", ParseConfig::default(), &[]);
+        let res = try_parse_entries_inner(b"\x14\x07Unknown", ParseConfig::default(), &[], 0);
         assert_eq!(res, None);
     }
+
+    #[test]
+    fn try_parse_entries_records_spans() {
+        // 1: 150, 3 { 2: "ab" }
+        let entries =
+            try_parse_entries(b"\x08\x96\x01\x1a\x04\x12\x02ab", ParseConfig::default()).unwrap();
+        let spans: Vec<(Vec<u64>, FieldSpan)> = entries
+            .into_iter()
+            .map(|entry| (entry.path, entry.span))
+            .collect();
+        let outer = FieldSpan {
+            number: 3,
+            wire_type: 2,
+            start: 3,
+            tag_len: 1,
+            length_len: 1,
+            payload: 5..9,
+            end_tag_len: 0,
+            overlong: vec![],
+        };
+        assert_eq!(
+            spans,
+            vec![
+                (
+                    vec![1],
+                    FieldSpan {
+                        number: 1,
+                        wire_type: 0,
+                        start: 0,
+                        tag_len: 1,
+                        length_len: 0,
+                        payload: 1..3,
+                        end_tag_len: 0,
+                        overlong: vec![],
+                    }
+                ),
+                (vec![3], outer.clone()),
+                (
+                    vec![3, 2],
+                    FieldSpan {
+                        number: 2,
+                        wire_type: 2,
+                        start: 5,
+                        tag_len: 1,
+                        length_len: 1,
+                        payload: 7..9,
+                        end_tag_len: 0,
+                        overlong: vec![],
+                    }
+                ),
+                (vec![3], outer),
+            ]
+        );
+    }
+
+    #[test]
+    fn try_parse_entries_parses_groups() {
+        // 1 (group) { 2: 1 }, 3 (empty group)
+        let entries =
+            try_parse_entries(b"\x0b\x10\x01\x0c\x1b\x1c", ParseConfig::default()).unwrap();
+        let values: Vec<(Vec<u64>, EntryValue)> = entries
+            .into_iter()
+            .map(|entry| (entry.path, entry.value))
+            .collect();
+        assert_eq!(
+            values,
+            vec![
+                (vec![1], EntryValue::OpenNested),
+                (vec![1, 2], EntryValue::Varint(1)),
+                (vec![1], EntryValue::CloseNested),
+                (vec![3], EntryValue::OpenNested),
+                (vec![3], EntryValue::CloseNested),
+            ]
+        );
+
+        // A group in a length-delimited field
+        let entries = try_parse_entries(b"\x12\x02\x0b\x0c", ParseConfig::default()).unwrap();
+        assert_eq!(entries[1].value, EntryValue::OpenNested);
+        assert_eq!(entries[1].span.start, 2);
+        assert_eq!(entries[1].span.end(), 4);
+    }
+
+    #[test]
+    fn parse_entries_reports_errors() {
+        assert_eq!(parse_entries(b"", ParseConfig::default()), Ok(vec![]));
+        assert_eq!(
+            parse_entries(b"\x08\x01\x12\x05ab", ParseConfig::default()),
+            Err(WireError {
+                offset: 2,
+                kind: WireErrorKind::TruncatedValue {
+                    needed: 5,
+                    available: 2
+                },
+            })
+        );
+        // Invalid nested messages are bytes, but errors in groups are reported
+        // relative to the input
+        assert!(parse_entries(b"\x12\x01\x08", ParseConfig::default()).is_ok());
+        assert_eq!(
+            parse_entries(b"\x08\x01\x0b\x10", ParseConfig::default())
+                .unwrap_err()
+                .offset,
+            4
+        );
+
+        let config = ParseConfig {
+            no_fixed64: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            parse_entries(b"\x08\x01\x19\x00\x00\x00\x00\x00\x00\x00\x00", config),
+            Err(WireError {
+                offset: 2,
+                kind: WireErrorKind::ExcludedWireType(1),
+            })
+        );
+    }
+
+    /// A message nested `depth` times in field 1 with `1: 1` in the innermost message
+    fn nested(depth: usize) -> Vec<u8> {
+        let mut bytes = b"\x08\x01".to_vec();
+        for _ in 0..depth {
+            let mut outer = vec![0x0a];
+            let mut len = bytes.len();
+            while len >= 0x80 {
+                outer.push(len as u8 | 0x80);
+                len >>= 7;
+            }
+            outer.push(len as u8);
+            outer.extend(bytes);
+            bytes = outer;
+        }
+        bytes
+    }
+
+    #[test]
+    fn try_parse_entries_stops_at_max_depth() {
+        let config = ParseConfig {
+            max_depth: 2,
+            ..Default::default()
+        };
+        let bytes = nested(2);
+        let entries = try_parse_entries(&bytes, config).unwrap();
+        assert!(entries.iter().all(|e| e.limit.is_none()));
+        let bytes = nested(3);
+        let entries = try_parse_entries(&bytes, config).unwrap();
+        let last = entries.iter().find(|e| e.path.len() == 3).unwrap();
+        assert_eq!(last.value, EntryValue::Bytes(b"\x08\x01"));
+        assert_eq!(last.limit, Some(ParseLimit::Depth));
+
+        // Deep nesting does not overflow the stack
+        let bytes = nested(1_000);
+        let entries = try_parse_entries(&bytes, ParseConfig::default()).unwrap();
+        let limited: Vec<&Entry> = entries.iter().filter(|e| e.limit.is_some()).collect();
+        assert_eq!(limited.len(), 1);
+        assert_eq!(limited[0].path.len(), 101);
+        let mut groups = b"\x0b".repeat(1_000);
+        groups.extend(b"\x0c".repeat(1_000));
+        let entries = try_parse_entries(&groups, ParseConfig::default()).unwrap();
+        assert_eq!(entries.len(), 201);
+        assert_eq!(entries[100].path.len(), 101);
+        assert_eq!(entries[100].limit, Some(ParseLimit::Depth));
+    }
+
+    #[test]
+    fn try_parse_entries_stops_when_work_is_used_up() {
+        // 1 { 1: 1 }, 2 { 1: 1 }, 3 { 1: 1 }
+        let bytes = b"\x0a\x02\x08\x01\x12\x02\x08\x01\x1a\x02\x08\x01";
+        let config = ParseConfig {
+            max_work: 5,
+            ..Default::default()
+        };
+        let entries = try_parse_entries(bytes, config).unwrap();
+        let values: Vec<(u64, &EntryValue, Option<ParseLimit>)> = entries
+            .iter()
+            .map(|e| (e.path[0], &e.value, e.limit))
+            .collect();
+        assert_eq!(
+            values,
+            vec![
+                (1, &EntryValue::OpenNested, None),
+                (1, &EntryValue::Varint(1), None),
+                (1, &EntryValue::CloseNested, None),
+                (2, &EntryValue::OpenNested, None),
+                (2, &EntryValue::Varint(1), None),
+                (2, &EntryValue::CloseNested, None),
+                (3, &EntryValue::Bytes(b"\x08\x01"), Some(ParseLimit::Work)),
+            ]
+        );
+    }
+
+    #[test]
+    fn try_parse_entries_rejects_low_confidence_messages() {
+        // 1: a hash that is a valid serialization with large field numbers
+        let mut bytes = b"\x0a\x20".to_vec();
+        bytes.extend(
+            hex::decode("30bef3ee80d750103175f44b825869b0d230cdc1adced509dbd600fcaa8abe0e")
+                .unwrap(),
+        );
+        let entries = try_parse_entries(&bytes, ParseConfig::default()).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].value, EntryValue::Bytes(&bytes[2..]));
+        assert!(entries[0].confidence.unwrap() < 0.5);
+
+        let config = ParseConfig {
+            min_confidence: 0.0,
+            ..Default::default()
+        };
+        let entries = try_parse_entries(&bytes, config).unwrap();
+        assert_eq!(entries[0].value, EntryValue::OpenNested);
+        assert_eq!(entries[0].confidence, entries.last().unwrap().confidence);
+
+        // 1 { 1: 1 }, 2: "ab"
+        let entries =
+            try_parse_entries(b"\x0a\x02\x08\x01\x12\x02ab", ParseConfig::default()).unwrap();
+        assert_eq!(entries[0].confidence, Some(1.0));
+        assert_eq!(entries[3].confidence, None);
+    }
+
+    #[test]
+    fn try_parse_entries_nests_messages_of_fixed_width_fields() {
+        // 1 { 1: 1.0, 2: 1.0, ..., 7: 1.0 } with doubles
+        let mut bytes = vec![0x0a, 63];
+        for number in 1..=7u8 {
+            bytes.push(number << 3 | 1);
+            bytes.extend(1f64.to_le_bytes());
+        }
+        let entries = try_parse_entries(&bytes, ParseConfig::default()).unwrap();
+        assert_eq!(entries.len(), 9);
+        assert_eq!(entries[0].value, EntryValue::OpenNested);
+        assert_eq!(entries[1].value, EntryValue::Fixed64(1f64.to_le_bytes()));
+    }
+
+    #[test]
+    fn try_parse_entries_rejects_invalid_field_numbers() {
+        let is_nested = |bytes: &[u8], config: ParseConfig| {
+            try_parse_entries(bytes, config).unwrap()[0].value == EntryValue::OpenNested
+        };
+        let config = ParseConfig::default();
+        // 1 { 1: 1 }
+        assert!(is_nested(b"\x0a\x02\x08\x01", config));
+        // 1 { 0: 1 }
+        assert!(!is_nested(b"\x0a\x02\x00\x01", config));
+        // 1 { 536870912: 1 }
+        assert!(!is_nested(b"\x0a\x06\x80\x80\x80\x80\x10\x01", config));
+        // 1 { 536870911: 1 }
+        let bytes = b"\x0a\x06\xf8\xff\xff\xff\x0f\x01";
+        let config = ParseConfig {
+            min_confidence: 0.0,
+            ..Default::default()
+        };
+        assert!(is_nested(bytes, config));
+
+        let config = ParseConfig {
+            max_field_number: 100,
+            ..Default::default()
+        };
+        // 1 { 100: 1 }
+        assert!(is_nested(b"\x0a\x03\xa0\x06\x01", config));
+        // 1 { 101: 1 }
+        assert!(!is_nested(b"\x0a\x03\xa8\x06\x01", config));
+    }
 }
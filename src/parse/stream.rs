@@ -0,0 +1,278 @@
+//! Reading the top level fields of a message from a stream without
+//! holding the whole input in memory.
+
+use std::io::{self, BufRead, Read};
+
+use super::wire::{fits_64_bits, VarintPart, WireError, WireErrorKind, MAX_VARINT_LEN};
+
+/// A complete serialized top level field as read by [`FieldReader`]
+#[derive(Debug, PartialEq)]
+pub struct StreamedField<'a> {
+    /// Offset of the tag in the stream
+    pub offset: usize,
+    /// The tag and value of the field
+    pub bytes: &'a [u8],
+}
+
+/// Reads the top level fields of a message one at a time.
+///
+/// Only the current field is kept in memory. Its bytes are not validated beyond
+/// what is needed to find its end, so they should be parsed with
+/// [`Message::parse_at`](crate::Message::parse_at).
+pub struct FieldReader<R> {
+    reader: R,
+    /// Offset of the next byte in the stream
+    offset: usize,
+    buffer: Vec<u8>,
+}
+
+impl<R: BufRead> FieldReader<R> {
+    pub fn new(reader: R) -> Self {
+        FieldReader {
+            reader,
+            offset: 0,
+            buffer: Vec::new(),
+        }
+    }
+
+    /// Reads the next field. Returns `None` at the end of the stream.
+    ///
+    /// Invalid or truncated fields are reported as errors of kind
+    /// `InvalidData` containing a [`WireError`].
+    pub fn next_field(&mut self) -> io::Result<Option<StreamedField<'_>>> {
+        self.buffer.clear();
+        let start = self.offset;
+        if self.reader.fill_buf()?.is_empty() {
+            return Ok(None);
+        }
+        let (number, wire_type) = self.read_field()?;
+        if wire_type == 4 {
+            return Err(invalid(start, WireErrorKind::UnexpectedEndGroup(number)));
+        }
+        Ok(Some(StreamedField {
+            offset: start,
+            bytes: &self.buffer,
+        }))
+    }
+
+    /// Appends the next field to the buffer and returns its number and wire type
+    fn read_field(&mut self) -> io::Result<(u64, u8)> {
+        let start = self.offset;
+        let (number, wire_type) = self.read_tag_and_value()?;
+        if wire_type == 3 {
+            self.read_group(start, number)?;
+        }
+        Ok((number, wire_type))
+    }
+
+    /// Appends a tag and the value following it. For a start group tag only
+    /// the tag is read.
+    fn read_tag_and_value(&mut self) -> io::Result<(u64, u8)> {
+        let start = self.offset;
+        let tag = self.read_varint(VarintPart::Tag)?;
+        let number = (tag >> 3) as u64;
+        let wire_type = (tag & 0x07) as u8;
+        match wire_type {
+            0 => {
+                self.read_varint(VarintPart::Value)?;
+            }
+            1 => self.read_bytes(start, 8)?,
+            2 => {
+                let length = self.read_varint(VarintPart::Length)?;
+                self.read_bytes(start, length)?;
+            }
+            3 | 4 => {}
+            5 => self.read_bytes(start, 4)?,
+            _ => return Err(invalid(start, WireErrorKind::InvalidWireType(wire_type))),
+        }
+        Ok((number, wire_type))
+    }
+
+    /// Appends the fields of a group up to and including its end group tag.
+    /// Nested groups are tracked with a stack like in `read_fields`.
+    fn read_group(&mut self, start: usize, number: u64) -> io::Result<()> {
+        let mut open = vec![number];
+        while let Some(&innermost) = open.last() {
+            if self.reader.fill_buf()?.is_empty() {
+                return Err(invalid(start, WireErrorKind::UnterminatedGroup(number)));
+            }
+            let field_start = self.offset;
+            match self.read_tag_and_value()? {
+                (nested, 3) => open.push(nested),
+                (end, 4) if end == innermost => {
+                    open.pop();
+                }
+                (end, 4) => {
+                    return Err(invalid(field_start, WireErrorKind::UnexpectedEndGroup(end)))
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Appends a varint to the buffer and returns its value.
+    /// Like `read_varint` it must fit into 64 bits.
+    fn read_varint(&mut self, part: VarintPart) -> io::Result<u128> {
+        let start = self.offset;
+        let mut value = 0u128;
+        for i in 0..MAX_VARINT_LEN {
+            let mut byte = [0];
+            if self.reader.read(&mut byte)? == 0 {
+                return Err(invalid(start, WireErrorKind::TruncatedVarint(part)));
+            }
+            self.buffer.push(byte[0]);
+            self.offset += 1;
+            value |= ((byte[0] & 0x7f) as u128) << (i * 7);
+            if byte[0] & 0x80 == 0 {
+                if !fits_64_bits(i, byte[0]) {
+                    break;
+                }
+                return Ok(value);
+            }
+        }
+        Err(invalid(start, WireErrorKind::VarintTooLong(part)))
+    }
+
+    /// Appends `len` bytes to the buffer. The field starting at `start` is reported
+    /// as truncated if the stream ends before.
+    fn read_bytes(&mut self, start: usize, len: u128) -> io::Result<()> {
+        let limit = u64::try_from(len).unwrap_or(u64::MAX);
+        let read = (&mut self.reader)
+            .take(limit)
+            .read_to_end(&mut self.buffer)?;
+        self.offset += read;
+        if read as u128 != len {
+            return Err(invalid(
+                start,
+                WireErrorKind::TruncatedValue {
+                    needed: len,
+                    available: read,
+                },
+            ));
+        }
+        Ok(())
+    }
+}
+
+fn invalid(offset: usize, kind: WireErrorKind) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, WireError { offset, kind })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Reads all fields and returns their offsets and bytes
+    fn read_all(bytes: &[u8]) -> io::Result<Vec<(usize, Vec<u8>)>> {
+        let mut reader = FieldReader::new(bytes);
+        let mut out = Vec::new();
+        while let Some(field) = reader.next_field()? {
+            out.push((field.offset, field.bytes.to_vec()));
+        }
+        Ok(out)
+    }
+
+    fn wire_error(bytes: &[u8]) -> WireError {
+        let error = read_all(bytes).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        error
+            .into_inner()
+            .unwrap()
+            .downcast::<WireError>()
+            .map(|error| *error)
+            .unwrap()
+    }
+
+    #[test]
+    fn field_reader_works() {
+        // 1: 150, 2: "ab", 3: fixed64, 4 (group) { 1: 1 }, 5: fixed32
+        let bytes = b"\x08\x96\x01\x12\x02ab\x19\x01\x02\x03\x04\x05\x06\x07\x08\
+                      \x23\x08\x01\x24\x2d\x01\x02\x03\x04";
+        assert_eq!(
+            read_all(bytes).unwrap(),
+            vec![
+                (0, b"\x08\x96\x01".to_vec()),
+                (3, b"\x12\x02ab".to_vec()),
+                (7, b"\x19\x01\x02\x03\x04\x05\x06\x07\x08".to_vec()),
+                (16, b"\x23\x08\x01\x24".to_vec()),
+                (20, b"\x2d\x01\x02\x03\x04".to_vec()),
+            ]
+        );
+        assert_eq!(read_all(b"").unwrap(), vec![]);
+    }
+
+    #[test]
+    fn field_reader_reports_errors() {
+        assert_eq!(
+            wire_error(b"\x08\x01\x12\x05ab"),
+            WireError {
+                offset: 2,
+                kind: WireErrorKind::TruncatedValue {
+                    needed: 5,
+                    available: 2
+                },
+            }
+        );
+        assert_eq!(
+            wire_error(b"\x08\x96"),
+            WireError {
+                offset: 1,
+                kind: WireErrorKind::TruncatedVarint(VarintPart::Value),
+            }
+        );
+        assert_eq!(
+            wire_error(b"\x0e"),
+            WireError {
+                offset: 0,
+                kind: WireErrorKind::InvalidWireType(6),
+            }
+        );
+        assert_eq!(
+            wire_error(b"\x08\x01\x80\x80\x80\x80\x80\x80\x80\x80\x80\x10\x01"),
+            WireError {
+                offset: 2,
+                kind: WireErrorKind::VarintTooLong(VarintPart::Tag),
+            }
+        );
+        let mut bytes = vec![0x08];
+        bytes.extend([0xff; 19]);
+        bytes.push(0x7f);
+        assert_eq!(
+            wire_error(&bytes),
+            WireError {
+                offset: 1,
+                kind: WireErrorKind::VarintTooLong(VarintPart::Value),
+            }
+        );
+        assert_eq!(
+            wire_error(b"\x0c"),
+            WireError {
+                offset: 0,
+                kind: WireErrorKind::UnexpectedEndGroup(1),
+            }
+        );
+        assert_eq!(
+            wire_error(b"\x0b\x08\x01\x14"),
+            WireError {
+                offset: 3,
+                kind: WireErrorKind::UnexpectedEndGroup(2),
+            }
+        );
+        assert_eq!(
+            wire_error(b"\x0b\x08\x01"),
+            WireError {
+                offset: 0,
+                kind: WireErrorKind::UnterminatedGroup(1),
+            }
+        );
+        // A huge length is not allocated up front
+        assert_eq!(
+            wire_error(b"\x12\xff\xff\xff\xff\xff\xff\xff\xff\x7fab").kind,
+            WireErrorKind::TruncatedValue {
+                needed: u64::MAX as u128 >> 1,
+                available: 2
+            }
+        );
+    }
+}
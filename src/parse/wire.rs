@@ -0,0 +1,659 @@
+//! Low level scanning of the protobuf wire format to find out
+//! where fields are located in the serialization.
+
+use std::fmt;
+use std::ops::Range;
+
+/// The location of a single field in a serialized message.
+///
+/// All offsets are relative to the start of the scanned bytes.
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct FieldSpan {
+    pub number: u64,
+    pub wire_type: u8,
+    /// Offset of the first byte of the tag
+    pub start: usize,
+    /// Number of bytes of the tag varint
+    pub tag_len: usize,
+    /// Number of bytes of the length prefix. Only non-zero for wire type 2.
+    pub length_len: usize,
+    /// The location of the value. For groups these are the fields between the tags.
+    pub payload: Range<usize>,
+    /// Number of bytes of the end group tag. Only non-zero for wire type 3.
+    pub end_tag_len: usize,
+    /// The varints of this field that use more bytes than necessary.
+    /// This is valid protobuf but not the canonical encoding.
+    pub overlong: Vec<VarintPart>,
+}
+
+/// The varints that can be part of a field
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum VarintPart {
+    Tag,
+    Length,
+    Value,
+}
+
+impl VarintPart {
+    pub fn name(self) -> &'static str {
+        match self {
+            VarintPart::Tag => "tag",
+            VarintPart::Length => "length",
+            VarintPart::Value => "value",
+        }
+    }
+}
+
+impl FieldSpan {
+    /// Moves the span by the given number of bytes. This is used to make the offsets
+    /// of nested messages relative to the outermost message.
+    pub fn shifted(self, offset: usize) -> Self {
+        FieldSpan {
+            start: self.start + offset,
+            payload: self.payload.start + offset..self.payload.end + offset,
+            ..self
+        }
+    }
+
+    /// Offset of the first byte after the field
+    pub fn end(&self) -> usize {
+        self.payload.end + self.end_tag_len
+    }
+}
+
+/// Why bytes are not a valid serialization
+#[derive(Debug, PartialEq, Clone)]
+pub enum WireErrorKind {
+    /// The bytes end within a varint
+    TruncatedVarint(VarintPart),
+    /// The value is longer than the remaining bytes
+    TruncatedValue { needed: u128, available: usize },
+    /// Wire types 6 and 7 are not defined
+    InvalidWireType(u8),
+    /// An end group tag without a matching start group tag
+    UnexpectedEndGroup(u64),
+    /// The bytes end before the end group tag
+    UnterminatedGroup(u64),
+    /// The wire type is valid but excluded by the `ParseConfig`
+    ExcludedWireType(u8),
+    /// The varint is longer than 10 bytes or its value does not fit into 64 bits
+    VarintTooLong(VarintPart),
+}
+
+/// An error with the offset of the field or varint at which reading failed
+#[derive(Debug, PartialEq, Clone)]
+pub struct WireError {
+    pub offset: usize,
+    pub kind: WireErrorKind,
+}
+
+impl WireError {
+    /// Moves the offset by the given number of bytes like [`FieldSpan::shifted`]
+    pub fn shifted(self, offset: usize) -> Self {
+        WireError {
+            offset: self.offset + offset,
+            ..self
+        }
+    }
+}
+
+impl fmt::Display for WireErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WireErrorKind::TruncatedVarint(part) => write!(f, "truncated {} varint", part.name()),
+            WireErrorKind::TruncatedValue { needed, available } => write!(
+                f,
+                "value needs {} bytes but only {} are left",
+                needed, available
+            ),
+            WireErrorKind::InvalidWireType(wire_type) => {
+                write!(f, "invalid wire type {}", wire_type)
+            }
+            WireErrorKind::UnexpectedEndGroup(number) => {
+                write!(f, "end group tag of field {} without start", number)
+            }
+            WireErrorKind::UnterminatedGroup(number) => {
+                write!(f, "group of field {} is not terminated", number)
+            }
+            WireErrorKind::ExcludedWireType(wire_type) => write!(
+                f,
+                "wire type {} ({}) is excluded",
+                wire_type,
+                wire_type_name(*wire_type)
+            ),
+            WireErrorKind::VarintTooLong(part) => {
+                write!(f, "{} varint does not fit into 64 bits", part.name())
+            }
+        }
+    }
+}
+
+impl fmt::Display for WireError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at offset {}", self.kind, self.offset)
+    }
+}
+
+impl std::error::Error for WireError {}
+
+/// Returns a human readable name for the wire type
+pub fn wire_type_name(wire_type: u8) -> &'static str {
+    match wire_type {
+        0 => "varint",
+        1 => "64 bit",
+        2 => "length-delimited",
+        3 => "start group",
+        4 => "end group",
+        5 => "32 bit",
+        _ => "invalid",
+    }
+}
+
+/// Reads the top level fields of the bytes. A group is returned as a single
+/// field containing everything up to its end group tag.
+pub fn read_fields(bytes: &[u8]) -> Result<Vec<FieldSpan>, WireError> {
+    let mut out = Vec::new();
+    let mut pos = 0;
+    while pos < bytes.len() {
+        let span = read_field(bytes, pos)?;
+        if span.wire_type == 4 {
+            return Err(WireError {
+                offset: pos,
+                kind: WireErrorKind::UnexpectedEndGroup(span.number),
+            });
+        }
+        pos = span.end();
+        out.push(span);
+    }
+    Ok(out)
+}
+
+/// Scans the top level fields of the bytes.
+///
+/// Scanning stops at the first field that cannot be read completely.
+pub fn scan_fields(bytes: &[u8]) -> Vec<FieldSpan> {
+    let mut out = Vec::new();
+    let mut pos = 0;
+    while pos < bytes.len() {
+        match read_field(bytes, pos) {
+            Ok(span) if span.wire_type != 4 => {
+                pos = span.end();
+                out.push(span);
+            }
+            _ => break,
+        }
+    }
+    out
+}
+
+/// Reads the field starting at `start`. An end group tag is returned as a field
+/// with an empty payload, so the caller can decide if it is expected.
+fn read_field(bytes: &[u8], start: usize) -> Result<FieldSpan, WireError> {
+    let span = read_tag_and_value(bytes, start)?;
+    if span.wire_type == 3 {
+        read_group(bytes, span)
+    } else {
+        Ok(span)
+    }
+}
+
+/// Reads the tag and the value following it. For a start group tag only
+/// the tag is read.
+fn read_tag_and_value(bytes: &[u8], start: usize) -> Result<FieldSpan, WireError> {
+    let varint = |offset: usize, part: VarintPart| {
+        let len = varint_len(&bytes[offset..], part).map_err(|kind| WireError { offset, kind })?;
+        Ok((read_varint(&bytes[offset..]).unwrap().0, len))
+    };
+    let (tag, tag_len) = varint(start, VarintPart::Tag)?;
+    let value_start = start + tag_len;
+    let number = (tag >> 3) as u64;
+    let wire_type = (tag & 0x07) as u8;
+    let mut overlong = Vec::new();
+    if is_overlong(&bytes[start..value_start]) {
+        overlong.push(VarintPart::Tag);
+    }
+    let (length_len, value_len) = match wire_type {
+        0 => {
+            let (_, value_len) = varint(value_start, VarintPart::Value)?;
+            if is_overlong(&bytes[value_start..value_start + value_len]) {
+                overlong.push(VarintPart::Value);
+            }
+            (0, value_len as u128)
+        }
+        1 => (0, 8),
+        2 => {
+            let (length, length_len) = varint(value_start, VarintPart::Length)?;
+            if is_overlong(&bytes[value_start..value_start + length_len]) {
+                overlong.push(VarintPart::Length);
+            }
+            (length_len, length)
+        }
+        3 | 4 => (0, 0),
+        5 => (0, 4),
+        _ => {
+            return Err(WireError {
+                offset: start,
+                kind: WireErrorKind::InvalidWireType(wire_type),
+            })
+        }
+    };
+    let payload_start = value_start + length_len;
+    let available = bytes.len() - payload_start;
+    let payload_end = match usize::try_from(value_len) {
+        Ok(len) if len <= available => payload_start + len,
+        _ => {
+            return Err(WireError {
+                offset: start,
+                kind: WireErrorKind::TruncatedValue {
+                    needed: value_len,
+                    available,
+                },
+            })
+        }
+    };
+    Ok(FieldSpan {
+        number,
+        wire_type,
+        start,
+        tag_len,
+        length_len,
+        payload: payload_start..payload_end,
+        end_tag_len: 0,
+        overlong,
+    })
+}
+
+/// Reads the fields of a group up to the end group tag with the same number.
+/// The span is the start group tag as returned by `read_tag_and_value`.
+///
+/// Nested groups are tracked with a stack instead of recursion, so deeply
+/// nested input cannot overflow the call stack.
+fn read_group(bytes: &[u8], mut span: FieldSpan) -> Result<FieldSpan, WireError> {
+    // The numbers of the groups that are not closed yet
+    let mut open = vec![span.number];
+    let mut pos = span.payload.end;
+    while pos < bytes.len() {
+        let field = read_tag_and_value(bytes, pos)?;
+        match field.wire_type {
+            3 => open.push(field.number),
+            4 if open.last() != Some(&field.number) => {
+                return Err(WireError {
+                    offset: pos,
+                    kind: WireErrorKind::UnexpectedEndGroup(field.number),
+                });
+            }
+            4 => {
+                open.pop();
+                if open.is_empty() {
+                    if !field.overlong.is_empty() && !span.overlong.contains(&VarintPart::Tag) {
+                        span.overlong.push(VarintPart::Tag);
+                    }
+                    span.payload.end = pos;
+                    span.end_tag_len = field.tag_len;
+                    return Ok(span);
+                }
+            }
+            _ => {}
+        }
+        pos = field.end();
+    }
+    Err(WireError {
+        offset: span.start,
+        kind: WireErrorKind::UnterminatedGroup(span.number),
+    })
+}
+
+/// Reads a varint and returns its value and length in bytes.
+/// Returns `None` if the bytes end within the varint or it does not fit into 64 bits.
+pub fn read_varint(bytes: &[u8]) -> Option<(u128, usize)> {
+    let len = varint_len(bytes, VarintPart::Value).ok()?;
+    let mut value = 0u128;
+    for (i, byte) in bytes[..len].iter().enumerate() {
+        value |= ((byte & 0x7f) as u128) << (i * 7);
+    }
+    Some((value, len))
+}
+
+/// Checks if the encoded varint uses more bytes than necessary,
+/// i.e. it ends with a zero byte after a continuation bit.
+fn is_overlong(varint: &[u8]) -> bool {
+    varint.len() > 1 && varint.last() == Some(&0)
+}
+
+/// The maximum number of bytes of a varint. Protobuf encodes at most 64 bits.
+pub(super) const MAX_VARINT_LEN: usize = 10;
+
+/// The number of bytes of the varint at the start of the bytes.
+/// `part` is only used for the error.
+fn varint_len(bytes: &[u8], part: VarintPart) -> Result<usize, WireErrorKind> {
+    let end = bytes
+        .iter()
+        .take(MAX_VARINT_LEN)
+        .position(|byte| byte & 0x80 == 0);
+    match end {
+        Some(i) if !fits_64_bits(i, bytes[i]) => Err(WireErrorKind::VarintTooLong(part)),
+        Some(i) => Ok(i + 1),
+        None if bytes.len() < MAX_VARINT_LEN => Err(WireErrorKind::TruncatedVarint(part)),
+        None => Err(WireErrorKind::VarintTooLong(part)),
+    }
+}
+
+/// Checks if the byte at the given index of a varint keeps its value within
+/// 64 bits. Only the lowest bit of the tenth byte is left for the value.
+pub(super) fn fits_64_bits(index: usize, byte: u8) -> bool {
+    index + 1 < MAX_VARINT_LEN || byte & 0x7f <= 1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn read_varint_works() {
+        assert_eq!(read_varint(b""), None);
+        assert_eq!(read_varint(b"\x00"), Some((0, 1)));
+        assert_eq!(read_varint(b"\x01"), Some((1, 1)));
+        assert_eq!(read_varint(b"\x7f"), Some((127, 1)));
+        assert_eq!(read_varint(b"\x96\x01"), Some((150, 2)));
+        assert_eq!(read_varint(b"\x96\x01\x05"), Some((150, 2)));
+        // Over-long encoding of 0
+        assert_eq!(read_varint(b"\x80\x80\x00"), Some((0, 3)));
+        // Incomplete
+        assert_eq!(read_varint(b"\x96"), None);
+        assert_eq!(read_varint(b"\x96\x81"), None);
+    }
+
+    #[test]
+    fn scan_fields_works() {
+        // 1: 150, 2: "ab", 3: fixed64, 4: fixed32
+        let bytes =
+            b"\x08\x96\x01\x12\x02ab\x19\x01\x02\x03\x04\x05\x06\x07\x08\x25\x01\x02\x03\x04";
+        assert_eq!(
+            scan_fields(bytes),
+            vec![
+                FieldSpan {
+                    number: 1,
+                    wire_type: 0,
+                    start: 0,
+                    tag_len: 1,
+                    length_len: 0,
+                    payload: 1..3,
+                    end_tag_len: 0,
+                    overlong: vec![],
+                },
+                FieldSpan {
+                    number: 2,
+                    wire_type: 2,
+                    start: 3,
+                    tag_len: 1,
+                    length_len: 1,
+                    payload: 5..7,
+                    end_tag_len: 0,
+                    overlong: vec![],
+                },
+                FieldSpan {
+                    number: 3,
+                    wire_type: 1,
+                    start: 7,
+                    tag_len: 1,
+                    length_len: 0,
+                    payload: 8..16,
+                    end_tag_len: 0,
+                    overlong: vec![],
+                },
+                FieldSpan {
+                    number: 4,
+                    wire_type: 5,
+                    start: 16,
+                    tag_len: 1,
+                    length_len: 0,
+                    payload: 17..21,
+                    end_tag_len: 0,
+                    overlong: vec![],
+                },
+            ]
+        );
+
+        // Multi-byte tag: field 1000, wire type 0
+        let spans = scan_fields(b"\xc0\x3e\x01");
+        assert_eq!(spans.len(), 1);
+        assert_eq!(spans[0].number, 1000);
+        assert_eq!(spans[0].tag_len, 2);
+        assert_eq!(spans[0].payload, 2..3);
+    }
+
+    #[test]
+    fn scan_fields_detects_overlong_varints() {
+        // Minimal
+        assert_eq!(scan_fields(b"\x08\x00")[0].overlong, vec![]);
+        assert_eq!(scan_fields(b"\x08\x96\x01")[0].overlong, vec![]);
+        // Value 0 in two bytes
+        assert_eq!(
+            scan_fields(b"\x08\x80\x00")[0].overlong,
+            vec![VarintPart::Value]
+        );
+        // Tag with redundant continuation byte
+        assert_eq!(
+            scan_fields(b"\x88\x00\x01")[0].overlong,
+            vec![VarintPart::Tag]
+        );
+        // Length prefix
+        assert_eq!(
+            scan_fields(b"\x12\x82\x00ab")[0].overlong,
+            vec![VarintPart::Length]
+        );
+        assert_eq!(
+            scan_fields(b"\x92\x80\x00\x82\x00ab")[0].overlong,
+            vec![VarintPart::Tag, VarintPart::Length]
+        );
+    }
+
+    #[test]
+    fn field_span_shifted_works() {
+        let span = FieldSpan {
+            number: 2,
+            wire_type: 2,
+            start: 3,
+            tag_len: 1,
+            length_len: 1,
+            payload: 5..7,
+            end_tag_len: 0,
+            overlong: vec![],
+        };
+        assert_eq!(
+            span.shifted(10),
+            FieldSpan {
+                number: 2,
+                wire_type: 2,
+                start: 13,
+                tag_len: 1,
+                length_len: 1,
+                payload: 15..17,
+                end_tag_len: 0,
+                overlong: vec![],
+            }
+        );
+    }
+
+    #[test]
+    fn scan_fields_stops_at_incomplete() {
+        assert_eq!(scan_fields(b""), vec![]);
+        // Length exceeds data
+        assert_eq!(scan_fields(b"\x12\x05ab"), vec![]);
+        let spans = scan_fields(b"\x08\x01\x12\x05ab");
+        assert_eq!(spans.len(), 1);
+        // Invalid wire type
+        assert_eq!(scan_fields(b"\x08\x01\x0e"), scan_fields(b"\x08\x01"));
+        // End group without start
+        assert_eq!(scan_fields(b"\x0c"), vec![]);
+    }
+
+    #[test]
+    fn read_fields_reads_groups() {
+        // 1: 1, 2 (group) { 3: 1, 4 (group) { } }, 5: 1
+        let bytes = b"\x08\x01\x13\x18\x01\x23\x24\x14\x28\x01";
+        let spans = read_fields(bytes).unwrap();
+        assert_eq!(spans.len(), 3);
+        assert_eq!(
+            spans[1],
+            FieldSpan {
+                number: 2,
+                wire_type: 3,
+                start: 2,
+                tag_len: 1,
+                length_len: 0,
+                payload: 3..7,
+                end_tag_len: 1,
+                overlong: vec![],
+            }
+        );
+        assert_eq!(spans[1].end(), 8);
+        assert_eq!(spans[2].start, 8);
+        assert_eq!(read_fields(&bytes[3..7]).unwrap().len(), 2);
+
+        // Over-long end group tag
+        assert_eq!(
+            read_fields(b"\x0b\x8c\x00").unwrap()[0].overlong,
+            vec![VarintPart::Tag]
+        );
+    }
+
+    #[test]
+    fn read_fields_reports_errors() {
+        let error = |bytes: &[u8]| read_fields(bytes).unwrap_err();
+        assert_eq!(read_fields(b""), Ok(vec![]));
+        assert_eq!(
+            error(b"\x08\x01\x08"),
+            WireError {
+                offset: 3,
+                kind: WireErrorKind::TruncatedVarint(VarintPart::Value),
+            }
+        );
+        assert_eq!(
+            error(b"\x08\x01\x96"),
+            WireError {
+                offset: 2,
+                kind: WireErrorKind::TruncatedVarint(VarintPart::Tag),
+            }
+        );
+        assert_eq!(
+            error(b"\x12\x05ab"),
+            WireError {
+                offset: 0,
+                kind: WireErrorKind::TruncatedValue {
+                    needed: 5,
+                    available: 2
+                },
+            }
+        );
+        assert_eq!(
+            error(b"\x08\x01\x19\x00"),
+            WireError {
+                offset: 2,
+                kind: WireErrorKind::TruncatedValue {
+                    needed: 8,
+                    available: 1
+                },
+            }
+        );
+        assert_eq!(
+            error(b"\x0e"),
+            WireError {
+                offset: 0,
+                kind: WireErrorKind::InvalidWireType(6),
+            }
+        );
+        assert_eq!(
+            error(b"\x08\x01\x14"),
+            WireError {
+                offset: 2,
+                kind: WireErrorKind::UnexpectedEndGroup(2),
+            }
+        );
+        // End group of another field inside a group
+        assert_eq!(
+            error(b"\x0b\x14\x0c"),
+            WireError {
+                offset: 1,
+                kind: WireErrorKind::UnexpectedEndGroup(2),
+            }
+        );
+        assert_eq!(
+            error(b"\x08\x01\x0b\x08\x01"),
+            WireError {
+                offset: 2,
+                kind: WireErrorKind::UnterminatedGroup(1),
+            }
+        );
+        // Errors inside a group are reported at their position
+        assert_eq!(error(b"\x0b\x08").offset, 2);
+    }
+
+    #[test]
+    fn read_fields_checks_varint_width() {
+        let error = |bytes: &[u8]| read_fields(bytes).unwrap_err();
+        // 1: u64::MAX
+        let max = b"\x08\xff\xff\xff\xff\xff\xff\xff\xff\xff\x01";
+        assert_eq!(read_varint(&max[1..]), Some((u64::MAX as u128, 10)));
+        assert!(read_fields(max).is_ok());
+        // 1: u64::MAX + 1
+        assert_eq!(
+            error(b"\x08\x80\x80\x80\x80\x80\x80\x80\x80\x80\x02"),
+            WireError {
+                offset: 1,
+                kind: WireErrorKind::VarintTooLong(VarintPart::Value),
+            }
+        );
+        // 20 bytes with a value of 128 bits
+        let mut bytes = vec![0x08];
+        bytes.extend([0xff; 19]);
+        bytes.push(0x7f);
+        assert_eq!(read_varint(&bytes[1..]), None);
+        assert_eq!(
+            error(&bytes),
+            WireError {
+                offset: 1,
+                kind: WireErrorKind::VarintTooLong(VarintPart::Value),
+            }
+        );
+        // Even over-long encodings of small values have at most 10 bytes
+        let mut tag = vec![0x88];
+        tag.extend([0x80; 9]);
+        tag.extend(b"\x00\x01");
+        assert_eq!(
+            error(&tag),
+            WireError {
+                offset: 0,
+                kind: WireErrorKind::VarintTooLong(VarintPart::Tag),
+            }
+        );
+        // The length prefix is checked as well
+        assert_eq!(
+            error(b"\x0a\xff\xff\xff\xff\xff\xff\xff\xff\xff\xff\x01").kind,
+            WireErrorKind::VarintTooLong(VarintPart::Length)
+        );
+        // A varint that is too long cannot be truncated
+        assert_eq!(
+            error(&[0x08, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff]).kind,
+            WireErrorKind::VarintTooLong(VarintPart::Value)
+        );
+    }
+
+    #[test]
+    fn wire_error_display_works() {
+        let error = WireError {
+            offset: 3,
+            kind: WireErrorKind::TruncatedVarint(VarintPart::Length),
+        };
+        assert_eq!(error.to_string(), "truncated length varint at offset 3");
+        assert_eq!(error.shifted(2).offset, 5);
+        assert_eq!(
+            WireErrorKind::ExcludedWireType(1).to_string(),
+            "wire type 1 (64 bit) is excluded"
+        );
+        assert_eq!(
+            WireErrorKind::VarintTooLong(VarintPart::Tag).to_string(),
+            "tag varint does not fit into 64 bits"
+        );
+    }
+}
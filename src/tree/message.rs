@@ -0,0 +1,262 @@
+//! A tree representation of decoded messages.
+
+use std::collections::HashMap;
+
+use crate::parse::{
+    direct_fields, parse_entries, parse_entries_at, try_parse_entries, Entry, EntryValue,
+    FieldSpan, ParseConfig, ParseLimit, WireError,
+};
+
+/// A decoded message. Byte values borrow from the decoded input.
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct Message<'a> {
+    /// The fields in the order of the serialization
+    pub fields: Vec<Field<'a>>,
+}
+
+/// A single field of a message
+#[derive(Debug, PartialEq, Clone)]
+pub struct Field<'a> {
+    pub number: u64,
+    pub value: FieldValue<'a>,
+    /// The location of the field in the decoded bytes
+    pub span: FieldSpan,
+    /// Set for bytes that were not tried as nested message because a limit was reached
+    pub limit: Option<ParseLimit>,
+    /// For length-delimited fields that are a valid serialization: how likely
+    /// the payload is a message. Bytes with a confidence were rejected as message.
+    pub confidence: Option<f64>,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum FieldValue<'a> {
+    /// Wire type 0 used for int32, int64, uint32, uint64, sint32, sint64, bool, enum
+    Varint(u128),
+    /// Wire type 1 used for fixed64, sfixed64, double
+    Fixed64([u8; 8]),
+    /// Wire type 5 used for fixed32, sfixed32, float
+    Fixed32([u8; 4]),
+    /// Wire type 2 content that could not be decoded as message, i.e. strings or bytes
+    Bytes(&'a [u8]),
+    /// Wire type 2 content that was decoded as message
+    Message(Message<'a>),
+}
+
+impl<'a> Message<'a> {
+    /// Decodes the bytes. Returns `None` if they are not a valid protobuf serialization.
+    pub fn decode(bytes: &'a [u8], config: ParseConfig) -> Option<Self> {
+        try_parse_entries(bytes, config).map(|entries| Self::from_entries(&entries))
+    }
+
+    /// Decodes the bytes like [`Message::decode`] but reports where and why they are
+    /// not a valid serialization. Empty bytes are an empty message.
+    pub fn parse(bytes: &'a [u8], config: ParseConfig) -> Result<Self, WireError> {
+        parse_entries(bytes, config).map(|entries| Self::from_entries(&entries))
+    }
+
+    /// Decodes bytes found at the offset of a larger input, e.g. a field read with a
+    /// [`FieldReader`](crate::FieldReader). Spans and errors are relative to that input.
+    pub fn parse_at(
+        bytes: &'a [u8],
+        config: ParseConfig,
+        offset: usize,
+    ) -> Result<Self, WireError> {
+        parse_entries_at(bytes, config, offset).map(|entries| Self::from_entries(&entries))
+    }
+
+    /// Builds the tree from the flat entries as returned by `try_parse_entries`
+    pub fn from_entries(entries: &[Entry<'a>]) -> Self {
+        let fields = direct_fields(entries)
+            .into_iter()
+            .map(|(start, end)| {
+                let entry = &entries[start];
+                let value = match &entry.value {
+                    EntryValue::Varint(v) => FieldValue::Varint(*v),
+                    EntryValue::Fixed64(v) => FieldValue::Fixed64(*v),
+                    EntryValue::Fixed32(v) => FieldValue::Fixed32(*v),
+                    EntryValue::Bytes(v) => FieldValue::Bytes(v),
+                    EntryValue::OpenNested | EntryValue::CloseNested => {
+                        FieldValue::Message(Self::from_entries(&entries[start + 1..end]))
+                    }
+                };
+                Field {
+                    number: *entry.path.last().unwrap(),
+                    value,
+                    span: entry.span.clone(),
+                    limit: entry.limit,
+                    confidence: entry.confidence,
+                }
+            })
+            .collect();
+        Message { fields }
+    }
+
+    /// The first field with the given number
+    pub fn get(&self, number: u64) -> Option<&Field<'a>> {
+        self.fields.iter().find(|field| field.number == number)
+    }
+
+    /// All fields with the given number, e.g. the elements of a repeated field
+    pub fn get_all(&self, number: u64) -> impl Iterator<Item = &Field<'a>> {
+        self.fields
+            .iter()
+            .filter(move |field| field.number == number)
+    }
+
+    /// Iterates over all fields including the fields of nested messages, depth-first
+    /// in the order of the serialization. Each field comes with its path.
+    pub fn walk(&self) -> Walk<'_, 'a> {
+        Walk {
+            stack: vec![(Vec::new(), self.fields.iter())],
+        }
+    }
+}
+
+impl<'a> Field<'a> {
+    /// The nested message if this field was decoded as one
+    pub fn as_message(&self) -> Option<&Message<'a>> {
+        match &self.value {
+            FieldValue::Message(message) => Some(message),
+            _ => None,
+        }
+    }
+}
+
+/// The position of each field among the fields with the same number
+pub(crate) fn field_indices(fields: &[Field]) -> Vec<usize> {
+    let mut counts = HashMap::<u64, usize>::new();
+    fields
+        .iter()
+        .map(|field| {
+            let count = counts.entry(field.number).or_default();
+            *count += 1;
+            *count - 1
+        })
+        .collect()
+}
+
+/// Iterator returned by [`Message::walk`]
+pub struct Walk<'m, 'a> {
+    /// The path of each message being iterated and its remaining fields
+    stack: Vec<(Vec<u64>, std::slice::Iter<'m, Field<'a>>)>,
+}
+
+impl<'m, 'a> Iterator for Walk<'m, 'a> {
+    type Item = (Vec<u64>, &'m Field<'a>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let (parent, fields) = self.stack.last_mut()?;
+            match fields.next() {
+                Some(field) => {
+                    let mut path = parent.clone();
+                    path.push(field.number);
+                    if let FieldValue::Message(message) = &field.value {
+                        self.stack.push((path.clone(), message.fields.iter()));
+                    }
+                    return Some((path, field));
+                }
+                None => {
+                    self.stack.pop();
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 1: 150, 2 { 1: 1, 3 { 4: "ab" } }, 1: 2
+    const MESSAGE: &[u8] = b"\x08\x96\x01\x12\x08\x08\x01\x1a\x04\x22\x02ab\x08\x02";
+
+    fn message() -> Message<'static> {
+        Message::decode(MESSAGE, ParseConfig::default()).unwrap()
+    }
+
+    #[test]
+    fn message_decode_works() {
+        let message = message();
+        assert_eq!(message.fields.len(), 3);
+        assert_eq!(message.fields[0].number, 1);
+        assert_eq!(message.fields[0].value, FieldValue::Varint(150));
+        assert_eq!(message.fields[0].span.payload, 1..3);
+
+        let nested = message.fields[1].as_message().unwrap();
+        assert_eq!(nested.fields[0].value, FieldValue::Varint(1));
+        let inner = nested.fields[1].as_message().unwrap();
+        assert_eq!(inner.fields[0].number, 4);
+        assert_eq!(inner.fields[0].value, FieldValue::Bytes(b"ab"));
+        assert_eq!(inner.fields[0].span.payload, 11..13);
+
+        assert_eq!(Message::decode(b"\x08", ParseConfig::default()), None);
+    }
+
+    #[test]
+    fn message_parse_works() {
+        assert_eq!(
+            Message::parse(MESSAGE, ParseConfig::default()),
+            Ok(message())
+        );
+        assert_eq!(
+            Message::parse(b"", ParseConfig::default()),
+            Ok(Message::default())
+        );
+        let error = Message::parse(b"\x08\x01\x08", ParseConfig::default()).unwrap_err();
+        assert_eq!(error.offset, 3);
+
+        // 1 (group) { 2: 1 }
+        let message = Message::parse(b"\x0b\x10\x01\x0c", ParseConfig::default()).unwrap();
+        let group = message.fields[0].as_message().unwrap();
+        assert_eq!(group.fields[0].value, FieldValue::Varint(1));
+        assert_eq!(message.fields[0].span.end(), 4);
+
+        let message = Message::parse_at(b"\x08\x01", ParseConfig::default(), 10).unwrap();
+        assert_eq!(message.fields[0].span.start, 10);
+        let error = Message::parse_at(b"\x08", ParseConfig::default(), 10).unwrap_err();
+        assert_eq!(error.offset, 11);
+    }
+
+    #[test]
+    fn message_get_works() {
+        let message = message();
+        assert_eq!(message.get(1).unwrap().value, FieldValue::Varint(150));
+        assert_eq!(message.get(5), None);
+        let values: Vec<&FieldValue> = message.get_all(1).map(|field| &field.value).collect();
+        assert_eq!(
+            values,
+            vec![&FieldValue::Varint(150), &FieldValue::Varint(2)]
+        );
+        assert_eq!(message.get(1).unwrap().as_message(), None);
+    }
+
+    #[test]
+    fn field_indices_works() {
+        assert_eq!(field_indices(&message().fields), vec![0, 0, 1]);
+        // 1: 1, 2: 1, 1: 1, 1: 1
+        let message = Message::decode(b"\x08\x01\x10\x01\x08\x01\x08\x01", ParseConfig::default());
+        assert_eq!(field_indices(&message.unwrap().fields), vec![0, 0, 1, 2]);
+    }
+
+    #[test]
+    fn message_walk_works() {
+        let message = message();
+        let paths: Vec<Vec<u64>> = message.walk().map(|(path, _)| path).collect();
+        assert_eq!(
+            paths,
+            vec![
+                vec![1],
+                vec![2],
+                vec![2, 1],
+                vec![2, 3],
+                vec![2, 3, 4],
+                vec![1]
+            ]
+        );
+        let (_, field) = message.walk().nth(4).unwrap();
+        assert_eq!(field.value, FieldValue::Bytes(b"ab"));
+
+        assert_eq!(Message::default().walk().count(), 0);
+    }
+}
@@ -0,0 +1,6 @@
+mod message;
+mod visitor;
+
+pub(crate) use message::field_indices;
+pub use message::{Field, FieldValue, Message, Walk};
+pub use visitor::Visitor;
@@ -0,0 +1,101 @@
+//! Traversal of decoded messages with callbacks.
+
+use super::message::{Field, FieldValue, Message};
+
+/// Callbacks for [`Message::accept`]. All methods do nothing by default,
+/// so implementations only need to override what they are interested in.
+///
+/// The path of a field contains the field numbers from the top level message
+/// down to the field itself.
+pub trait Visitor {
+    /// Called for every field that is not a nested message
+    fn visit_value(&mut self, _path: &[u64], _field: &Field) {}
+
+    /// Called for a nested message before its fields are visited
+    fn enter_message(&mut self, _path: &[u64], _field: &Field) {}
+
+    /// Called for a nested message after its fields were visited
+    fn leave_message(&mut self, _path: &[u64], _field: &Field) {}
+}
+
+impl Message<'_> {
+    /// Visits all fields depth-first in the order of the serialization
+    pub fn accept<V: Visitor + ?Sized>(&self, visitor: &mut V) {
+        accept_fields(&self.fields, &mut Vec::new(), visitor);
+    }
+}
+
+fn accept_fields<V: Visitor + ?Sized>(fields: &[Field], path: &mut Vec<u64>, visitor: &mut V) {
+    for field in fields {
+        path.push(field.number);
+        match &field.value {
+            FieldValue::Message(message) => {
+                visitor.enter_message(path, field);
+                accept_fields(&message.fields, path, visitor);
+                visitor.leave_message(path, field);
+            }
+            _ => visitor.visit_value(path, field),
+        }
+        path.pop();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse::ParseConfig;
+
+    /// Records the callbacks as text
+    #[derive(Default)]
+    struct Recorder(Vec<String>);
+
+    impl Visitor for Recorder {
+        fn visit_value(&mut self, path: &[u64], _field: &Field) {
+            self.0.push(format!("value {:?}", path));
+        }
+
+        fn enter_message(&mut self, path: &[u64], _field: &Field) {
+            self.0.push(format!("enter {:?}", path));
+        }
+
+        fn leave_message(&mut self, path: &[u64], _field: &Field) {
+            self.0.push(format!("leave {:?}", path));
+        }
+    }
+
+    #[test]
+    fn message_accept_works() {
+        // 1: 150, 2 { 1: 1, 3 { 4: "ab" } }, 1: 2
+        let bytes = b"\x08\x96\x01\x12\x08\x08\x01\x1a\x04\x22\x02ab\x08\x02";
+        let message = Message::decode(bytes, ParseConfig::default()).unwrap();
+        let mut recorder = Recorder::default();
+        message.accept(&mut recorder);
+        assert_eq!(
+            recorder.0,
+            &[
+                "value [1]",
+                "enter [2]",
+                "value [2, 1]",
+                "enter [2, 3]",
+                "value [2, 3, 4]",
+                "leave [2, 3]",
+                "leave [2]",
+                "value [1]",
+            ]
+        );
+    }
+
+    #[test]
+    fn visitor_methods_are_optional() {
+        struct CountValues(usize);
+        impl Visitor for CountValues {
+            fn visit_value(&mut self, _path: &[u64], _field: &Field) {
+                self.0 += 1;
+            }
+        }
+        let message = Message::decode(b"\x08\x01\x12\x02\x08\x01", ParseConfig::default()).unwrap();
+        let mut counter = CountValues(0);
+        message.accept(&mut counter);
+        assert_eq!(counter.0, 2);
+    }
+}
@@ -0,0 +1,489 @@
+//! State of the interactive explorer, independent of the terminal.
+
+use decode_raw::{
+    escape_string, read_varint, Field, FieldSpan, FieldValue, Message, ParseConfig, WireError,
+};
+
+/// Number of bytes shown for binary data in the tree
+const MAX_BYTES: usize = 64;
+
+/// Number of characters shown for strings in the tree
+const MAX_STRING: usize = 200;
+
+/// The ways a length-delimited field can be shown
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Interpretation {
+    Message,
+    String,
+    Bytes,
+    /// Packed repeated varints
+    Packed,
+}
+
+const INTERPRETATIONS: [Interpretation; 4] = [
+    Interpretation::Message,
+    Interpretation::String,
+    Interpretation::Bytes,
+    Interpretation::Packed,
+];
+
+/// A field in the tree
+#[derive(Debug, PartialEq)]
+pub struct Node<'a> {
+    pub path: Vec<u64>,
+    /// The location in the input
+    pub span: FieldSpan,
+    /// The decoded value. Only used for wire types 0, 1 and 5.
+    pub value: FieldValue<'a>,
+    /// How a length-delimited field is shown. `None` for all other wire types.
+    pub interpretation: Option<Interpretation>,
+    pub collapsed: bool,
+    /// The fields of a length-delimited field shown as message
+    pub children: Vec<Node<'a>>,
+}
+
+/// A visible line of the tree. `indices` leads from the top level to the node.
+#[derive(Debug, PartialEq)]
+pub struct Row {
+    pub indices: Vec<usize>,
+    pub depth: usize,
+}
+
+pub struct App<'a> {
+    pub input: &'a [u8],
+    config: ParseConfig,
+    pub nodes: Vec<Node<'a>>,
+    /// The visible rows, updated whenever a message is collapsed, expanded or reinterpreted
+    rows: Vec<Row>,
+    /// Index into the visible rows
+    pub selected: usize,
+    /// The first visible row of the tree
+    pub tree_scroll: usize,
+    /// The first row of the hex pane
+    pub hex_scroll: usize,
+    /// Feedback for the last action
+    pub status: String,
+}
+
+impl<'a> App<'a> {
+    /// Fails if the input is not a valid protobuf serialization. Empty input is an
+    /// empty message.
+    pub fn new(input: &'a [u8], config: ParseConfig) -> Result<Self, WireError> {
+        let message = Message::parse(input, config)?;
+        let nodes = build_nodes(&message.fields, &[], 0);
+        let mut app = App {
+            input,
+            config,
+            nodes,
+            rows: Vec::new(),
+            selected: 0,
+            tree_scroll: 0,
+            hex_scroll: 0,
+            status: String::new(),
+        };
+        app.update_rows();
+        Ok(app)
+    }
+
+    /// All rows that are not hidden in a collapsed message
+    pub fn rows(&self) -> &[Row] {
+        &self.rows
+    }
+
+    fn update_rows(&mut self) {
+        self.rows.clear();
+        collect_rows(&self.nodes, &mut Vec::new(), &mut self.rows);
+    }
+
+    pub fn node(&self, indices: &[usize]) -> &Node<'a> {
+        let mut node = &self.nodes[indices[0]];
+        for index in &indices[1..] {
+            node = &node.children[*index];
+        }
+        node
+    }
+
+    fn node_mut(&mut self, indices: &[usize]) -> &mut Node<'a> {
+        let mut node = &mut self.nodes[indices[0]];
+        for index in &indices[1..] {
+            node = &mut node.children[*index];
+        }
+        node
+    }
+
+    fn selected_indices(&self) -> Option<Vec<usize>> {
+        self.rows.get(self.selected).map(|row| row.indices.clone())
+    }
+
+    pub fn selected_node(&self) -> Option<&Node<'a>> {
+        self.selected_indices().map(|indices| self.node(&indices))
+    }
+
+    /// The path of the selected field in the syntax of the select argument, e.g. .3.2
+    pub fn selected_path(&self) -> Option<String> {
+        self.selected_node()
+            .map(|node| node.path.iter().map(|n| format!(".{}", n)).collect())
+    }
+
+    pub fn move_selection(&mut self, delta: isize) {
+        let last = self.rows.len().saturating_sub(1);
+        self.selected = self.selected.saturating_add_signed(delta).min(last);
+    }
+
+    pub fn select_last(&mut self) {
+        self.selected = self.rows.len().saturating_sub(1);
+    }
+
+    /// Collapses the selected message or selects the parent if there is nothing to collapse
+    pub fn collapse(&mut self) {
+        let Some(indices) = self.selected_indices() else {
+            return;
+        };
+        let node = self.node_mut(&indices);
+        if !node.children.is_empty() && !node.collapsed {
+            node.collapsed = true;
+            self.update_rows();
+        } else if indices.len() > 1 {
+            let parent = &indices[..indices.len() - 1];
+            if let Some(position) = self.rows.iter().position(|row| row.indices == parent) {
+                self.selected = position;
+            }
+        }
+    }
+
+    pub fn expand(&mut self) {
+        if let Some(indices) = self.selected_indices() {
+            self.node_mut(&indices).collapsed = false;
+            self.update_rows();
+        }
+    }
+
+    pub fn toggle_collapsed(&mut self) {
+        if let Some(indices) = self.selected_indices() {
+            let node = self.node_mut(&indices);
+            node.collapsed = !node.collapsed;
+            self.update_rows();
+        }
+    }
+
+    /// Switches the selected length-delimited field to the next interpretation
+    /// that is possible for its content
+    pub fn cycle_interpretation(&mut self) {
+        let Some(indices) = self.selected_indices() else {
+            return;
+        };
+        let config = self.config;
+        let (span, path, current) = {
+            let node = self.node(&indices);
+            match node.interpretation {
+                Some(current) => (node.span.clone(), node.path.clone(), current),
+                None => {
+                    self.status = "Only length-delimited fields can be interpreted".to_string();
+                    return;
+                }
+            }
+        };
+        let payload = &self.input[span.payload.clone()];
+        let start = INTERPRETATIONS.iter().position(|i| *i == current).unwrap();
+        for offset in 1..INTERPRETATIONS.len() {
+            let next = INTERPRETATIONS[(start + offset) % INTERPRETATIONS.len()];
+            let children = match next {
+                Interpretation::Message => match parse_nested(payload, &span, &path, config) {
+                    Some(children) => children,
+                    None => continue,
+                },
+                Interpretation::String if std::str::from_utf8(payload).is_err() => continue,
+                Interpretation::Packed if packed_varints(payload).is_none() => continue,
+                _ => Vec::new(),
+            };
+            let node = self.node_mut(&indices);
+            node.interpretation = Some(next);
+            node.collapsed = false;
+            node.children = children;
+            self.update_rows();
+            self.status = format!("Showing as {}", interpretation_name(next));
+            return;
+        }
+        self.status = "No other interpretation possible".to_string();
+    }
+
+    /// The text of a row in the tree
+    pub fn describe(&self, node: &Node) -> String {
+        let number = node.path.last().unwrap();
+        let Some(interpretation) = node.interpretation else {
+            return format!("{}: {}", number, describe_value(&node.value));
+        };
+        let payload = &self.input[node.span.payload.clone()];
+        match interpretation {
+            Interpretation::Message if node.collapsed => {
+                format!("▸ {} {{ … }} ({} bytes)", number, payload.len())
+            }
+            Interpretation::Message => format!("▾ {} {{ ({} bytes)", number, payload.len()),
+            Interpretation::String => {
+                let s = std::str::from_utf8(payload).unwrap_or_default();
+                let shown: String = s.chars().take(MAX_STRING).collect();
+                let ellipsis = if shown.len() < s.len() { "…" } else { "" };
+                format!(
+                    "{}: ({} bytes) {}{}",
+                    number,
+                    payload.len(),
+                    escape_string(&shown),
+                    ellipsis
+                )
+            }
+            Interpretation::Bytes => {
+                let ellipsis = if payload.len() > MAX_BYTES { "…" } else { "" };
+                format!(
+                    "{}: ({} bytes) {}{}",
+                    number,
+                    payload.len(),
+                    hex::encode(&payload[..payload.len().min(MAX_BYTES)]),
+                    ellipsis
+                )
+            }
+            Interpretation::Packed => {
+                let values: Vec<String> = packed_varints(payload)
+                    .unwrap_or_default()
+                    .iter()
+                    .map(|v| v.to_string())
+                    .collect();
+                format!(
+                    "{}: ({} bytes, packed) [{}]",
+                    number,
+                    payload.len(),
+                    values.join(", ")
+                )
+            }
+        }
+    }
+}
+
+pub fn interpretation_name(interpretation: Interpretation) -> &'static str {
+    match interpretation {
+        Interpretation::Message => "message",
+        Interpretation::String => "string",
+        Interpretation::Bytes => "bytes",
+        Interpretation::Packed => "packed",
+    }
+}
+
+/// Builds the nodes for the fields of a message at the given path.
+/// The spans are moved by `offset` to make them relative to the input.
+fn build_nodes<'a>(fields: &[Field<'a>], path: &[u64], offset: usize) -> Vec<Node<'a>> {
+    fields
+        .iter()
+        .map(|field| {
+            let field_path = [path, &[field.number]].concat();
+            let (interpretation, children) = match &field.value {
+                FieldValue::Message(message) => (
+                    Some(Interpretation::Message),
+                    build_nodes(&message.fields, &field_path, offset),
+                ),
+                FieldValue::Bytes(v) if std::str::from_utf8(v).is_ok() => {
+                    (Some(Interpretation::String), Vec::new())
+                }
+                FieldValue::Bytes(_) => (Some(Interpretation::Bytes), Vec::new()),
+                _ => (None, Vec::new()),
+            };
+            Node {
+                path: field_path,
+                span: field.span.clone().shifted(offset),
+                value: field.value.clone(),
+                interpretation,
+                collapsed: false,
+                children,
+            }
+        })
+        .collect()
+}
+
+/// Parses the payload of the field at the given path and span as message
+fn parse_nested<'a>(
+    payload: &'a [u8],
+    span: &FieldSpan,
+    path: &[u64],
+    config: ParseConfig,
+) -> Option<Vec<Node<'a>>> {
+    let message = Message::decode(payload, config)?;
+    if message.fields.is_empty() {
+        return None;
+    }
+    Some(build_nodes(&message.fields, path, span.payload.start))
+}
+
+fn collect_rows(nodes: &[Node], indices: &mut Vec<usize>, out: &mut Vec<Row>) {
+    for (index, node) in nodes.iter().enumerate() {
+        indices.push(index);
+        out.push(Row {
+            indices: indices.clone(),
+            depth: indices.len() - 1,
+        });
+        if !node.collapsed {
+            collect_rows(&node.children, indices, out);
+        }
+        indices.pop();
+    }
+}
+
+/// Decodes the bytes as a sequence of varints
+fn packed_varints(bytes: &[u8]) -> Option<Vec<u128>> {
+    let mut out = Vec::new();
+    let mut pos = 0;
+    while pos < bytes.len() {
+        let (value, len) = read_varint(&bytes[pos..])?;
+        out.push(value);
+        pos += len;
+    }
+    if out.is_empty() {
+        return None;
+    }
+    Some(out)
+}
+
+fn describe_value(value: &FieldValue) -> String {
+    match value {
+        FieldValue::Varint(v) => v.to_string(),
+        FieldValue::Fixed64(v) => format!(
+            "(64 bit) {} / {}",
+            u64::from_le_bytes(*v),
+            f64::from_le_bytes(*v)
+        ),
+        FieldValue::Fixed32(v) => format!(
+            "(32 bit) {} / {}",
+            u32::from_le_bytes(*v),
+            f32::from_le_bytes(*v)
+        ),
+        FieldValue::Bytes(v) => format!("({} bytes)", v.len()),
+        FieldValue::Message(_) => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 1: 150, 2 { 1: 1, 2: "ab" }, 3: 9601 (not a message or string), 4: fixed32
+    const MESSAGE: &[u8] =
+        b"\x08\x96\x01\x12\x06\x08\x01\x12\x02ab\x1a\x02\x96\x01\x25\x00\x00\xc0\x3f";
+
+    fn app() -> App<'static> {
+        App::new(MESSAGE, ParseConfig::default()).unwrap()
+    }
+
+    fn described_rows(app: &App) -> Vec<String> {
+        app.rows()
+            .iter()
+            .map(|row| app.describe(app.node(&row.indices)))
+            .collect()
+    }
+
+    fn selected_interpretation(app: &App) -> Option<Interpretation> {
+        app.selected_node().unwrap().interpretation
+    }
+
+    #[test]
+    fn app_builds_tree() {
+        let app = app();
+        assert_eq!(
+            described_rows(&app),
+            &[
+                "1: 150",
+                "▾ 2 { (6 bytes)",
+                "1: 1",
+                "2: (2 bytes) \"ab\"",
+                "3: (2 bytes) 9601",
+                "4: (32 bit) 1069547520 / 1.5",
+            ]
+        );
+        assert_eq!(app.rows()[3].depth, 1);
+        assert_eq!(app.node(&[1, 1]).path, &[2, 2]);
+        assert_eq!(app.node(&[1, 1]).span.payload, 9..11);
+    }
+
+    #[test]
+    fn app_collapses_and_expands() {
+        let mut app = app();
+        app.move_selection(1);
+        app.collapse();
+        assert_eq!(app.rows().len(), 4);
+        assert_eq!(described_rows(&app)[1], "▸ 2 { … } (6 bytes)");
+        app.expand();
+        assert_eq!(app.rows().len(), 6);
+
+        // Collapsing a field without children selects the parent
+        app.move_selection(2);
+        assert_eq!(app.selected_path().unwrap(), ".2.2");
+        app.collapse();
+        assert_eq!(app.selected_path().unwrap(), ".2");
+    }
+
+    #[test]
+    fn app_moves_selection_within_rows() {
+        let mut app = app();
+        app.move_selection(-1);
+        assert_eq!(app.selected, 0);
+        app.move_selection(100);
+        assert_eq!(app.selected, 5);
+        assert_eq!(app.selected_path().unwrap(), ".4");
+    }
+
+    #[test]
+    fn app_cycles_interpretations() {
+        let mut app = app();
+        app.move_selection(1);
+        assert_eq!(selected_interpretation(&app), Some(Interpretation::Message));
+        app.cycle_interpretation();
+        assert_eq!(selected_interpretation(&app), Some(Interpretation::String));
+        assert_eq!(app.rows().len(), 4);
+        app.cycle_interpretation();
+        assert_eq!(selected_interpretation(&app), Some(Interpretation::Bytes));
+        assert_eq!(described_rows(&app)[1], "2: (6 bytes) 080112026162");
+        app.cycle_interpretation();
+        assert_eq!(selected_interpretation(&app), Some(Interpretation::Packed));
+        assert_eq!(
+            described_rows(&app)[1],
+            "2: (6 bytes, packed) [8, 1, 18, 2, 97, 98]"
+        );
+        app.cycle_interpretation();
+        assert_eq!(selected_interpretation(&app), Some(Interpretation::Message));
+        assert_eq!(app.rows().len(), 6);
+        let child = app.node(&[1, 1]);
+        assert_eq!(child.path, &[2, 2]);
+        assert_eq!(child.span.start, 7);
+        assert_eq!(child.span.payload, 9..11);
+
+        // Skips interpretations that are not possible
+        app.move_selection(3);
+        assert_eq!(app.selected_path().unwrap(), ".3");
+        app.cycle_interpretation();
+        assert_eq!(selected_interpretation(&app), Some(Interpretation::Packed));
+        assert_eq!(described_rows(&app)[4], "3: (2 bytes, packed) [150]");
+        app.cycle_interpretation();
+        assert_eq!(selected_interpretation(&app), Some(Interpretation::Bytes));
+
+        // Not length-delimited
+        app.move_selection(1);
+        app.cycle_interpretation();
+        assert_eq!(
+            app.status,
+            "Only length-delimited fields can be interpreted"
+        );
+    }
+
+    #[test]
+    fn app_accepts_empty_input() {
+        let mut app = App::new(b"", ParseConfig::default()).unwrap();
+        assert!(app.rows().is_empty());
+        app.move_selection(1);
+        app.collapse();
+        assert_eq!(app.selected_node(), None);
+        assert!(App::new(b"\x0a", ParseConfig::default()).is_err());
+    }
+
+    #[test]
+    fn packed_varints_works() {
+        assert_eq!(packed_varints(b""), None);
+        assert_eq!(packed_varints(b"\x01\x96\x01\x00"), Some(vec![1, 150, 0]));
+        assert_eq!(packed_varints(b"\x01\x96"), None);
+    }
+}
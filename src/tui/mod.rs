@@ -0,0 +1,77 @@
+//! An interactive explorer for large messages (--tui).
+
+mod app;
+mod ui;
+
+use std::io::{self, Write};
+
+use base64::Engine;
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use ratatui::DefaultTerminal;
+
+use decode_raw::ParseConfig;
+
+use app::App;
+
+/// Number of rows to move on page up and down
+const PAGE: isize = 20;
+
+/// Runs the explorer until the user quits. Key presses are read from the
+/// terminal, so the input can be piped in.
+pub fn run(input: &[u8], config: ParseConfig) -> io::Result<()> {
+    let mut app = App::new(input, config).map_err(|error| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "Input bytes is not a valid protobuf serialization: {}",
+                error
+            ),
+        )
+    })?;
+    let mut terminal = ratatui::init();
+    let result = event_loop(&mut terminal, &mut app);
+    ratatui::restore();
+    result
+}
+
+fn event_loop(terminal: &mut DefaultTerminal, app: &mut App) -> io::Result<()> {
+    loop {
+        terminal.draw(|frame| ui::draw(frame, app))?;
+        let Event::Key(key) = event::read()? else {
+            continue;
+        };
+        if key.kind != KeyEventKind::Press {
+            continue;
+        }
+        app.status.clear();
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => return Ok(()),
+            KeyCode::Down | KeyCode::Char('j') => app.move_selection(1),
+            KeyCode::Up | KeyCode::Char('k') => app.move_selection(-1),
+            KeyCode::PageDown => app.move_selection(PAGE),
+            KeyCode::PageUp => app.move_selection(-PAGE),
+            KeyCode::Home | KeyCode::Char('g') => app.selected = 0,
+            KeyCode::End | KeyCode::Char('G') => app.select_last(),
+            KeyCode::Left | KeyCode::Char('h') => app.collapse(),
+            KeyCode::Right | KeyCode::Char('l') => app.expand(),
+            KeyCode::Enter | KeyCode::Char(' ') => app.toggle_collapsed(),
+            KeyCode::Tab | KeyCode::Char('i') => app.cycle_interpretation(),
+            KeyCode::Char('c') | KeyCode::Char('y') => {
+                if let Some(path) = app.selected_path() {
+                    copy_to_clipboard(&path)?;
+                    app.status = format!("Copied {}", path);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Copies the text using the OSC 52 escape sequence, which most terminal emulators
+/// support, also over SSH.
+fn copy_to_clipboard(text: &str) -> io::Result<()> {
+    let encoded = base64::engine::general_purpose::STANDARD.encode(text);
+    let mut stdout = io::stdout();
+    write!(stdout, "\x1b]52;c;{}\x07", encoded)?;
+    stdout.flush()
+}
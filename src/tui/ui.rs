@@ -0,0 +1,106 @@
+//! Rendering of the explorer: the field tree, the hex pane and a status line.
+
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Borders, List, ListItem, ListState, Paragraph};
+use ratatui::Frame;
+
+use super::app::{interpretation_name, App};
+
+/// Number of bytes shown in one row of the hex pane
+const BYTES_PER_ROW: usize = 16;
+
+const HELP: &str = "↑↓ move  ←→ collapse/expand  tab interpretation  c copy path  q quit";
+
+pub fn draw(frame: &mut Frame, app: &mut App) {
+    let [main, status] =
+        Layout::vertical([Constraint::Min(1), Constraint::Length(1)]).areas(frame.area());
+    let [tree, hex] =
+        Layout::horizontal([Constraint::Percentage(50), Constraint::Percentage(50)]).areas(main);
+
+    // Only the visible rows are rendered
+    let height = (tree.height.saturating_sub(2) as usize).max(1);
+    if app.selected < app.tree_scroll {
+        app.tree_scroll = app.selected;
+    } else if app.selected >= app.tree_scroll + height {
+        app.tree_scroll = app.selected + 1 - height;
+    }
+    let items: Vec<ListItem> = app
+        .rows()
+        .iter()
+        .skip(app.tree_scroll)
+        .take(height)
+        .map(|row| {
+            let indent = "  ".repeat(row.depth);
+            ListItem::new(format!(
+                "{}{}",
+                indent,
+                app.describe(app.node(&row.indices))
+            ))
+        })
+        .collect();
+    let list = List::new(items)
+        .block(Block::default().borders(Borders::ALL).title("Fields"))
+        .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+    let mut list_state = ListState::default().with_selected(Some(app.selected - app.tree_scroll));
+    frame.render_stateful_widget(list, tree, &mut list_state);
+
+    let highlighted = app
+        .selected_node()
+        .map(|node| node.span.start..node.span.end())
+        .unwrap_or_default();
+    let height = hex.height.saturating_sub(2) as usize;
+    let first_row = highlighted.start / BYTES_PER_ROW;
+    if first_row < app.hex_scroll || first_row >= app.hex_scroll + height {
+        app.hex_scroll = first_row.saturating_sub(height / 4);
+    }
+    let lines: Vec<Line> = app
+        .input
+        .chunks(BYTES_PER_ROW)
+        .enumerate()
+        .skip(app.hex_scroll)
+        .take(height)
+        .map(|(row, chunk)| {
+            let offset = row * BYTES_PER_ROW;
+            let mut spans = vec![Span::styled(
+                format!("{:08x}: ", offset),
+                Style::default().fg(Color::DarkGray),
+            )];
+            for (i, byte) in chunk.iter().enumerate() {
+                let style = if highlighted.contains(&(offset + i)) {
+                    Style::default().bg(Color::Yellow).fg(Color::Black)
+                } else {
+                    Style::default()
+                };
+                spans.push(Span::styled(format!("{:02x}", byte), style));
+                spans.push(Span::raw(" "));
+            }
+            Line::from(spans)
+        })
+        .collect();
+    let hex_pane =
+        Paragraph::new(lines).block(Block::default().borders(Borders::ALL).title("Bytes"));
+    frame.render_widget(hex_pane, hex);
+
+    let mut info = Vec::new();
+    if let Some(node) = app.selected_node() {
+        info.push(app.selected_path().unwrap_or_default());
+        if let Some(interpretation) = node.interpretation {
+            info.push(format!("as {}", interpretation_name(interpretation)));
+        }
+        info.push(format!("@{}", node.span.start));
+    }
+    if !app.status.is_empty() {
+        info.push(app.status.clone());
+    }
+    let status_line = Line::from(vec![
+        Span::styled(
+            info.join("  "),
+            Style::default().add_modifier(Modifier::BOLD),
+        ),
+        Span::raw("  "),
+        Span::styled(HELP, Style::default().fg(Color::DarkGray)),
+    ]);
+    frame.render_widget(Paragraph::new(status_line), status);
+}